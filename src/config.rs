@@ -0,0 +1,77 @@
+
+use std::env;
+
+/// Runtime configuration, read from environment variables at startup
+#[derive(Debug, Clone)]
+pub struct Config {
+	/// PVGQL_CORS_ORIGINS, comma separated, '*' allows any origin
+	pub cors_allowed_origins: Vec<String>,
+	/// PVGQL_CORS_CREDENTIALS, whether browsers may send cookies cross origin
+	pub cors_allow_credentials: bool,
+	/// PVGQL_CORS_HEADERS, comma separated request headers allowed in addition to the defaults
+	pub cors_allowed_headers: Vec<String>,
+	/// PVGQL_CSRF_COOKIE, cookie holding the CSRF token
+	pub csrf_cookie: String,
+	/// PVGQL_CSRF_HEADER, header that must echo the CSRF cookie
+	pub csrf_header: String,
+	/// PVGQL_CSRF_CUSTOM_HEADER, presence of this header alone is also accepted since it can not be sent cross origin without CORS approval
+	pub csrf_custom_header: String,
+	/// PVGQL_UPLOAD_MAX_BYTES, max size of a single uploaded file
+	pub upload_max_bytes: usize,
+	/// PVGQL_UPLOAD_MAX_FILES, max num of files in a single request
+	pub upload_max_files: usize,
+	/// PVGQL_UPLOAD_MIME, comma separated MIME types accepted for uploads
+	pub upload_allowed_mime: Vec<String>,
+}
+
+fn env_list(key: &str, default: &str) -> Vec<String> {
+	env::var(key)
+		.unwrap_or_else(|_| default.to_string())
+		.split(',')
+		.map(|s| s.trim().to_string())
+		.filter(|s| s.len() > 0)
+		.collect::<Vec<_>>()
+}
+
+fn env_string(key: &str, default: &str) -> String {
+	env::var(key).unwrap_or_else(|_| default.to_string())
+}
+
+fn env_usize(key: &str, default: usize) -> usize {
+	env::var(key).ok().and_then(|v| v.parse::<usize>().ok()).unwrap_or(default)
+}
+
+fn env_bool(key: &str, default: bool) -> bool {
+	match env::var(key) {
+		Ok(v) => v == "1" || v.eq_ignore_ascii_case("true"),
+		Err(_) => default
+	}
+}
+
+#[cfg(debug_assertions)]
+const DEFAULT_CORS_ORIGINS: &str = "https://patchyvideo.com,https://www.patchyvideo.com,http://localhost:3000";
+
+#[cfg(not(debug_assertions))]
+const DEFAULT_CORS_ORIGINS: &str = "https://patchyvideo.com,https://www.patchyvideo.com";
+
+impl Config {
+	/// Fails if the combination of variables is unsafe
+	pub fn from_env() -> Result<Config, String> {
+		let config = Config {
+			cors_allowed_origins: env_list("PVGQL_CORS_ORIGINS", DEFAULT_CORS_ORIGINS),
+			cors_allow_credentials: env_bool("PVGQL_CORS_CREDENTIALS", true),
+			cors_allowed_headers: env_list("PVGQL_CORS_HEADERS", ""),
+			csrf_cookie: env_string("PVGQL_CSRF_COOKIE", "csrf_token"),
+			csrf_header: env_string("PVGQL_CSRF_HEADER", "X-CSRF-Token"),
+			csrf_custom_header: env_string("PVGQL_CSRF_CUSTOM_HEADER", "X-Requested-With"),
+			upload_max_bytes: env_usize("PVGQL_UPLOAD_MAX_BYTES", 5 * 1024 * 1024),
+			upload_max_files: env_usize("PVGQL_UPLOAD_MAX_FILES", 2),
+			upload_allowed_mime: env_list("PVGQL_UPLOAD_MIME", "image/png,image/jpeg,image/gif,image/webp"),
+		};
+		// any origin could send cookies along with the CSRF custom header
+		if config.cors_allow_credentials && config.cors_allowed_origins.iter().any(|o| o == "*") {
+			return Err("PVGQL_CORS_ORIGINS=* requires PVGQL_CORS_CREDENTIALS=false".to_string());
+		}
+		Ok(config)
+	}
+}
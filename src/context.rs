@@ -1,8 +1,122 @@
 
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::services::apiToken::ApiToken;
+use crate::services::forkPlaylist::ForkJob;
+use crate::services::relatedTags::CoOccurrenceSlot;
+use crate::services::upload::UploadedFile;
+use crate::services::users::Viewer;
+
+/// State shared by all requests
+#[derive(Debug, Default)]
+pub struct SharedState {
+	/// Running and recently finished playlist forks, keyed by job ID
+	pub fork_jobs: Mutex<HashMap<String, ForkJob>>,
+	/// Tag co-occurrences sampled without logging in, keyed by tag ID
+	pub related_tags: Mutex<HashMap<i32, CoOccurrenceSlot>>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Context {
 	pub session: Option<String>,
 	pub auth_header: Option<String>,
+	/// Personal API token used by this request, resolved at most once per request
+	pub api_token: Arc<Mutex<Option<Option<ApiToken>>>>,
+	/// Currently logged in user, resolved at most once per request
+	pub viewer: Arc<futures::lock::Mutex<Option<Option<Viewer>>>>,
+	/// Files sent with a multipart request, keyed by their name in `map`
+	pub uploads: Arc<HashMap<String, UploadedFile>>,
+	/// Default language of this request, used when a `lang` argument is omitted
+	pub lang: String,
+	pub shared: Arc<SharedState>,
+}
+
+impl Context {
+	pub fn new(session: Option<String>, auth_header: Option<String>, lang: String, shared: Arc<SharedState>) -> Context {
+		Context {
+			session,
+			auth_header,
+			api_token: Arc::new(Mutex::new(None)),
+			viewer: Arc::new(futures::lock::Mutex::new(None)),
+			uploads: Arc::new(HashMap::new()),
+			lang,
+			shared
+		}
+	}
+	/// Context of this request without session or credentials, for results shared between users
+	pub fn anonymous(&self) -> Context {
+		Context::new(None, None, self.lang.clone(), self.shared.clone())
+	}
+	/// `lang` if given, otherwise default language of this request
+	pub fn lang_or_default(&self, lang: Option<String>) -> String {
+		lang.unwrap_or_else(|| self.lang.clone())
+	}
 }
 
 impl juniper::Context for Context {}
+
+/// Language used when request does not specify one
+pub const DEFAULT_LANG: &str = "ENG";
+/// Language codes understood by backend
+const LANG_CODES: [&str; 18] = ["CHS", "CHT", "JPN", "ENG", "KOR", "CSY", "NLD", "FRA", "DEU", "HUN", "ITA", "PLK", "PTB", "ROM", "RUS", "ESP", "TRK", "VIN"];
+
+/// Map a language code such as 'CHS' or a language tag such as 'zh-CN' to backend language code
+fn lang_code(tag: &str) -> Option<&'static str> {
+	let tag = tag.trim();
+	if let Some(code) = LANG_CODES.iter().find(|c| c.eq_ignore_ascii_case(tag)) {
+		return Some(code);
+	}
+	let lower = tag.to_lowercase().replace('_', "-");
+	let mut parts = lower.split('-');
+	let primary = parts.next()?;
+	Some(match primary {
+		"zh" => {
+			if parts.any(|p| p == "tw" || p == "hk" || p == "mo" || p == "hant") { "CHT" } else { "CHS" }
+		},
+		"ja" => "JPN",
+		"en" => "ENG",
+		"ko" => "KOR",
+		"cs" => "CSY",
+		"nl" => "NLD",
+		"fr" => "FRA",
+		"de" => "DEU",
+		"hu" => "HUN",
+		"it" => "ITA",
+		"pl" => "PLK",
+		"pt" => "PTB",
+		"ro" => "ROM",
+		"ru" => "RUS",
+		"es" => "ESP",
+		"tr" => "TRK",
+		"vi" => "VIN",
+		_ => return None
+	})
+}
+
+/// Language from `lang` header, otherwise the most preferred supported language in `Accept-Language`
+pub fn request_language(lang_header: Option<&str>, accept_language: Option<&str>) -> String {
+	if let Some(code) = lang_header.and_then(lang_code) {
+		return code.to_string();
+	}
+	let mut candidates = accept_language.unwrap_or("").split(',').enumerate().filter_map(|(i, item)| {
+		let mut fields = item.split(';');
+		let tag = fields.next()?.trim();
+		let q = fields.filter_map(|f| f.trim().strip_prefix("q=").map(|v| v.trim().parse::<f32>().unwrap_or(0.0))).next().unwrap_or(1.0);
+		if q <= 0.0 {
+			return None;
+		}
+		Some((q, i, lang_code(tag)?))
+	}).collect::<Vec<_>>();
+	candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal).then(a.1.cmp(&b.1)));
+	candidates.first().map_or(DEFAULT_LANG, |c| c.2).to_string()
+}
+
+#[test]
+fn parse_request_language() {
+	assert_eq!(request_language(Some("chs"), Some("ja")), "CHS");
+	assert_eq!(request_language(Some("zh-Hant"), None), "CHT");
+	assert_eq!(request_language(None, Some("xx, ja;q=0.5, zh-TW;q=0.8")), "CHT");
+	assert_eq!(request_language(Some("klingon"), Some("de-DE,en;q=0.9")), "DEU");
+	assert_eq!(request_language(None, Some("*")), "ENG");
+}
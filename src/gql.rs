@@ -1,6 +1,6 @@
 use editTags::{ListTagParameters, listTags_impl};
 use juniper::{FieldResult, GraphQLSubscriptionValue};
-use juniper::RootNode;
+use juniper::{EmptyMutation, RootNode};
 
 use chrono::{DateTime, Utc};
 use notification::ListNotificationParameters;
@@ -13,8 +13,12 @@ use crate::{models, services::{comment::{self, GetThreadParameters, Thread}, pvs
 use crate::models::Error;
 use juniper::graphql_value;
 
-use crate::services::{authorDB, editTags, editVideo, getVideo, listVideo, notification, playlist, postvideo, rating, users, stats, leaderboard, tagHistory};
+use crate::services::apiToken;
+use crate::services::upload::{self, Upload};
+use crate::services::{authorDB, editTags, editVideo, explainQuery, getVideo, listVideo, notification, playlist, playlistFile, forkPlaylist, playlistCollaborators, postvideo, rating, search, users, stats, leaderboard, tagHistory, tagImplication, videoUrl};
 use crate::context::Context;
+use crate::guard::Guard;
+use crate::services::users::UserRoleEnum;
 
 pub struct Query;
 
@@ -30,6 +34,10 @@ impl Query {
 	pub async fn listVideo(context: &Context, para: listVideo::ListVideoParameters) -> FieldResult<listVideo::ListVideoResult> {
 		listVideo::listVideo_impl(context, para).await
 	}
+	/// Parse a tag query locally and resolve its terms, use this to find out why a query fails
+	pub async fn explainVideoQuery(context: &Context, query: String, lang: Option<String>) -> FieldResult<explainQuery::ExplainVideoQueryResult> {
+		explainQuery::explainVideoQuery_impl(context, query, lang).await
+	}
 	// ------------------------------------------------
 	//     getVideo
 	// ------------------------------------------------
@@ -40,6 +48,24 @@ impl Query {
 		getVideo::getRelatedVideo_impl(context, para).await
 	}
 	// ------------------------------------------------
+	//     search
+	// ------------------------------------------------
+	/// Search videos, playlists, tags, authors and users at once, `limit` applies to each type
+	pub async fn search(context: &Context, text: String, types: Option<Vec<search::SearchTypeEnum>>, limit: Option<i32>) -> FieldResult<search::SearchResult> {
+		search::search_impl(context, text, types, limit).await
+	}
+	// ------------------------------------------------
+	//     videoUrl
+	// ------------------------------------------------
+	/// Recognise site, canonical URL and unique_id of a video URL without contacting the backend
+	pub fn resolveVideoUrl(url: String) -> videoUrl::ResolveVideoUrlResult {
+		videoUrl::resolveVideoUrl_impl(url)
+	}
+	/// Look up already posted videos for each URL, result is in the same order as `urls`
+	pub async fn checkVideoExists(context: &Context, urls: Vec<String>) -> FieldResult<Vec<videoUrl::VideoExistsResult>> {
+		videoUrl::checkVideoExists_impl(context, urls).await
+	}
+	// ------------------------------------------------
 	//     editTags
 	// ------------------------------------------------
 	pub async fn getTagObjects(context: &Context, para: editTags::GetTagObjectsBatchParameters) -> FieldResult<Vec<models::TagObjectValue>> {
@@ -66,16 +92,35 @@ impl Query {
 	pub async fn listAdjacentVideos(context: &Context, para: playlist::ListAdjacentVideosParameters) -> FieldResult<Vec<models::VideoRank>> {
 		playlist::listAdjacentVideos_impl(context, para).await
 	}
+	/// Progress of your forkPlaylist job, null if unknown, expired or lost by a gateway restart
+	pub async fn forkProgress(context: &Context, job_id: String) -> FieldResult<Option<forkPlaylist::ForkJob>> {
+		forkPlaylist::getForkProgress_impl(context, job_id).await
+	}
+	/// Export playlist and all its videos as a file
+	pub async fn exportPlaylist(context: &Context, pid: String, format: playlistFile::PlaylistFileFormatEnum) -> FieldResult<playlistFile::PlaylistExport> {
+		playlistFile::exportPlaylist_impl(context, pid, format).await
+	}
 	// ------------------------------------------------
 	//     users
 	// ------------------------------------------------
 	pub async fn getUser(context: &Context, para: users::GetUserParameters) -> FieldResult<users::User> {
 		users::getUser_impl(context, para).await
 	}
+	/// Currently logged in user, null if not logged in
+	pub async fn viewer(context: &Context) -> FieldResult<Option<users::Viewer>> {
+		users::viewer_impl(context).await
+	}
+	#[deprecated(note = "Use `viewer` instead")]
 	pub async fn whoami(context: &Context) -> FieldResult<String> {
 		users::whoami_impl(context).await
 	}
 	// ------------------------------------------------
+	//     apiToken
+	// ------------------------------------------------
+	pub async fn listApiTokens(context: &Context) -> FieldResult<Vec<apiToken::ApiToken>> {
+		apiToken::listApiTokens_impl(context).await
+	}
+	// ------------------------------------------------
 	//     rating
 	// ------------------------------------------------
 	pub async fn getRating(context: &Context, para: rating::GetRatingParameters) -> FieldResult<Option<rating::Rating>> {
@@ -114,6 +159,10 @@ impl Query {
 	pub async fn getPopularTags(context: &Context, para: GetPopularTagsParameters) -> FieldResult<GetPopularTagsResult> {
 		tags::getPopularTags_impl(context, para).await
 	}
+	/// Tags with a name or alias starting with `prefix`, most used first
+	pub async fn autocompleteTags(context: &Context, prefix: String, lang: Option<String>, category: Option<models::TagCategoryEnum>, limit: Option<i32>) -> FieldResult<Vec<tags::TagSuggestion>> {
+		tags::autocompleteTags_impl(context, prefix, lang, category, limit).await
+	}
 	// ------------------------------------------------
 	//     stats
 	// ------------------------------------------------
@@ -156,18 +205,59 @@ impl Mutation {
 	pub async fn batchPostVideo(context: &Context, para: postvideo::BatchPostVideoRequestData) -> FieldResult<postvideo::BatchPostVideoResult> {
 		postvideo::batchPostVideo_impl(context, para).await
 	}
+	/// Post all parts of a multi-part video such as a bilibili video with multiple pages
+	pub async fn postVideoAllParts(context: &Context, para: postvideo::PostVideoRequestData) -> FieldResult<postvideo::BatchPostVideoResult> {
+		postvideo::postVideoAllParts_impl(context, para).await
+	}
 	// ------------------------------------------------
 	//     editVideo
 	// ------------------------------------------------
+	/// Edit tags and return all tags of video, use `editVideoTagsExpanded` to also add implied tags
+	/// and report which tags were added by implication, it is a separate mutation so this result type is kept
 	pub async fn editVideoTags(context: &Context, para: editVideo::EditVideoTagsParameters) -> FieldResult<Vec<models::TagObjectValue>> {
 		editVideo::editVideoTags_impl(context, para).await
 	}
+	/// Edit tags, then add tags implied by resulting tags unless `editBehaviour` is 'remove'
+	pub async fn editVideoTagsExpanded(context: &Context, para: editVideo::EditVideoTagsParameters) -> FieldResult<editVideo::EditVideoTagsResult> {
+		editVideo::editVideoTagsExpanded_impl(context, para).await
+	}
 	pub async fn editVideoTagIds(context: &Context, para: editVideo::EditVideoTagIdsParameters) -> FieldResult<Vec<models::TagObjectValue>> {
 		editVideo::editVideoTagIds_impl(context, para).await
 	}
 	pub async fn setVideoClearence(context: &Context, para: editVideo::SetVideoClearenceParameters) -> FieldResult<i32> {
+		guard!(context, Guard::Clearence(para.clearence.unwrap_or(0)));
 		editVideo::setVideoClearenceVideo_impl(context, para).await
 	}
+	pub async fn deleteVideo(context: &Context, para: editVideo::VideoOpParameters) -> FieldResult<models::Video> {
+		editVideo::deleteVideo_impl(context, para).await
+	}
+	pub async fn refreshVideoMetadata(context: &Context, para: editVideo::VideoOpParameters) -> FieldResult<models::Video> {
+		editVideo::refreshVideoMetadata_impl(context, para).await
+	}
+	pub async fn setRepostType(context: &Context, para: editVideo::SetRepostTypeParameters) -> FieldResult<models::Video> {
+		editVideo::setRepostType_impl(context, para).await
+	}
+	pub async fn linkCopies(context: &Context, para: editVideo::LinkCopiesParameters) -> FieldResult<Vec<models::Video>> {
+		editVideo::linkCopies_impl(context, para).await
+	}
+	pub async fn unlinkCopy(context: &Context, para: editVideo::VideoOpParameters) -> FieldResult<models::Video> {
+		editVideo::unlinkCopy_impl(context, para).await
+	}
+	pub async fn syncTagsAcrossCopies(context: &Context, para: editVideo::VideoOpParameters) -> FieldResult<models::Video> {
+		editVideo::syncTagsAcrossCopies_impl(context, para).await
+	}
+	// ------------------------------------------------
+	//     subscriptions
+	// ------------------------------------------------
+	pub async fn addSubscription(context: &Context, para: pvsubscription::SubscriptionParameters) -> FieldResult<pvsubscription::PVSubscription> {
+		pvsubscription::addSubscription_impl(context, para).await
+	}
+	pub async fn updateSubscription(context: &Context, subid: String, para: pvsubscription::SubscriptionParameters) -> FieldResult<pvsubscription::PVSubscription> {
+		pvsubscription::updateSubscription_impl(context, subid, para).await
+	}
+	pub async fn removeSubscription(context: &Context, subid: String) -> FieldResult<bool> {
+		pvsubscription::removeSubscription_impl(context, subid).await
+	}
 	// ------------------------------------------------
 	//     notification
 	// ------------------------------------------------
@@ -214,6 +304,7 @@ impl Mutation {
 		editTags::addTag_impl(context, para).await
 	}
 	pub async fn removeTag(context: &Context, para: editTags::RemoveTagParameters) -> FieldResult<bool> {
+		guard!(context, Guard::Role(UserRoleEnum::Admin));
 		editTags::removeTag_impl(context, para).await
 	}
 	pub async fn renameTag(context: &Context, para: editTags::RenameTagParameters) -> FieldResult<bool> {
@@ -235,8 +326,102 @@ impl Mutation {
 		editTags::addTagLanguage_impl(context, para).await
 	}
 	pub async fn mergeTag(context: &Context, para: editTags::MergeTagParameters) -> FieldResult<bool> {
+		guard!(context, Guard::Role(UserRoleEnum::Admin));
 		editTags::mergeTag_impl(context, para).await
 	}
+	/// Make tag `src` imply tag `dst`, so `dst` can be added automatically wherever `src` is used
+	pub async fn addTagImplication(context: &Context, para: tagImplication::TagImplicationParameters) -> FieldResult<bool> {
+		guard!(context, Guard::Role(UserRoleEnum::Admin));
+		tagImplication::addTagImplication_impl(context, para).await
+	}
+	pub async fn removeTagImplication(context: &Context, para: tagImplication::TagImplicationParameters) -> FieldResult<bool> {
+		guard!(context, Guard::Role(UserRoleEnum::Admin));
+		tagImplication::removeTagImplication_impl(context, para).await
+	}
+	// ------------------------------------------------
+	//     playlist
+	// ------------------------------------------------
+	pub async fn createPlaylist(context: &Context, para: playlist::CreatePlaylistParameters) -> FieldResult<models::Playlist> {
+		playlist::createPlaylist_impl(context, para).await
+	}
+	pub async fn updatePlaylist(context: &Context, para: playlist::UpdatePlaylistParameters) -> FieldResult<models::Playlist> {
+		playlist::updatePlaylist_impl(context, para).await
+	}
+	pub async fn deletePlaylist(context: &Context, pid: String) -> FieldResult<bool> {
+		playlist::deletePlaylist_impl(context, pid).await
+	}
+	pub async fn addVideoToPlaylist(context: &Context, para: playlist::AddVideoToPlaylistParameters) -> FieldResult<models::Playlist> {
+		playlist::addVideoToPlaylist_impl(context, para).await
+	}
+	pub async fn removeVideoFromPlaylist(context: &Context, para: playlist::RemoveVideoFromPlaylistParameters) -> FieldResult<models::Playlist> {
+		playlist::removeVideoFromPlaylist_impl(context, para).await
+	}
+	pub async fn movePlaylistVideo(context: &Context, para: playlist::MovePlaylistVideoParameters) -> FieldResult<models::Playlist> {
+		playlist::movePlaylistVideo_impl(context, para).await
+	}
+	pub async fn addToFavourites(context: &Context, vid: String) -> FieldResult<models::Playlist> {
+		playlist::addToFavourites_impl(context, vid).await
+	}
+	pub async fn removeFromFavourites(context: &Context, vid: String) -> FieldResult<models::Playlist> {
+		playlist::removeFromFavourites_impl(context, vid).await
+	}
+	pub async fn editPlaylistTags(context: &Context, para: playlist::EditPlaylistTagsParameters) -> FieldResult<Vec<models::TagObjectValue>> {
+		playlist::editPlaylistTags_impl(context, para).await
+	}
+	pub async fn editPlaylistTagIds(context: &Context, para: playlist::EditPlaylistTagIdsParameters) -> FieldResult<Vec<models::TagObjectValue>> {
+		playlist::editPlaylistTagIds_impl(context, para).await
+	}
+	/// Copy a playlist into a new one owned by current user, large playlists are copied in background
+	pub async fn forkPlaylist(context: &Context, para: forkPlaylist::ForkPlaylistParameters) -> FieldResult<forkPlaylist::ForkJob> {
+		forkPlaylist::forkPlaylist_impl(context, para).await
+	}
+	/// Continue an interrupted fork, videos are copied after the last video already in fork `pid`
+	pub async fn resumeFork(context: &Context, pid: String) -> FieldResult<forkPlaylist::ForkJob> {
+		forkPlaylist::resumeFork_impl(context, pid).await
+	}
+	pub async fn addPlaylistCollaborator(context: &Context, para: playlistCollaborators::PlaylistCollaboratorParameters) -> FieldResult<models::Playlist> {
+		playlistCollaborators::addPlaylistCollaborator_impl(context, para).await
+	}
+	pub async fn removePlaylistCollaborator(context: &Context, para: playlistCollaborators::PlaylistCollaboratorParameters) -> FieldResult<models::Playlist> {
+		playlistCollaborators::removePlaylistCollaborator_impl(context, para).await
+	}
+	/// Create a playlist from a file, every URL is posted as a video
+	pub async fn importPlaylist(context: &Context, para: playlistFile::ImportPlaylistParameters) -> FieldResult<playlistFile::ImportPlaylistResult> {
+		playlistFile::importPlaylist_impl(context, para).await
+	}
+	// ------------------------------------------------
+	//     rating
+	// ------------------------------------------------
+	pub async fn rateVideo(context: &Context, para: rating::RateVideoParameters) -> FieldResult<rating::Rating> {
+		rating::rateVideo_impl(context, para).await
+	}
+	pub async fn ratePlaylist(context: &Context, para: rating::RatePlaylistParameters) -> FieldResult<rating::Rating> {
+		rating::ratePlaylist_impl(context, para).await
+	}
+	pub async fn removeVideoRating(context: &Context, vid: String) -> FieldResult<rating::Rating> {
+		rating::removeVideoRating_impl(context, vid).await
+	}
+	pub async fn removePlaylistRating(context: &Context, pid: String) -> FieldResult<rating::Rating> {
+		rating::removePlaylistRating_impl(context, pid).await
+	}
+	// ------------------------------------------------
+	//     upload
+	// ------------------------------------------------
+	pub async fn uploadAvatar(context: &Context, file: Upload) -> FieldResult<users::User> {
+		upload::uploadAvatar_impl(context, file).await
+	}
+	pub async fn setPlaylistCover(context: &Context, pid: String, file: Upload) -> FieldResult<models::Playlist> {
+		upload::setPlaylistCover_impl(context, pid, file).await
+	}
+	// ------------------------------------------------
+	//     apiToken
+	// ------------------------------------------------
+	pub async fn createApiToken(context: &Context, para: apiToken::CreateApiTokenParameters) -> FieldResult<apiToken::CreateApiTokenResult> {
+		apiToken::createApiToken_impl(context, para).await
+	}
+	pub async fn revokeApiToken(context: &Context, token_id: String) -> FieldResult<bool> {
+		apiToken::revokeApiToken_impl(context, token_id).await
+	}
 }
 
 pub struct Subscription;
@@ -263,3 +448,10 @@ pub type Schema = RootNode<'static, Query, Mutation, Subscription>;
 pub fn create_schema() -> Schema {
 	Schema::new(Query {}, Mutation {}, Subscription {})
 }
+
+/// Served over GET, mutations are refused
+pub type ReadOnlySchema = RootNode<'static, Query, EmptyMutation<Context>, Subscription>;
+
+pub fn create_read_only_schema() -> ReadOnlySchema {
+	ReadOnlySchema::new(Query {}, EmptyMutation::new(), Subscription {})
+}
@@ -0,0 +1,61 @@
+use juniper::{FieldResult, graphql_value};
+
+use crate::context::Context;
+use crate::services::users::{self, UserRoleEnum, Viewer};
+
+/// Requirement on current user, checked before any backend call is made
+pub enum Guard {
+	/// Any logged in user that is not blocked
+	LoggedIn,
+	/// User must have at least this role, roles are ordered Blocked < Normal < Admin
+	Role(UserRoleEnum),
+	/// User must be able to see and set items of at least this clearence, as reported by backend
+	Clearence(i32)
+}
+
+pub fn forbidden(reason: &str) -> juniper::FieldError {
+	juniper::FieldError::new(
+		"FORBIDDEN",
+		graphql_value!({
+			"reason": reason
+		}),
+	)
+}
+
+impl Guard {
+	/// Returns current user if requirement is met
+	pub async fn check(&self, context: &Context) -> FieldResult<Viewer> {
+		let viewer = match users::viewer_impl(context).await? {
+			Some(v) => v,
+			None => return Err(forbidden("NOT_LOGGED_IN"))
+		};
+		if viewer.role == UserRoleEnum::Blocked {
+			return Err(forbidden("USER_BLOCKED"));
+		}
+		match self {
+			Guard::LoggedIn => {},
+			Guard::Role(role) => {
+				if viewer.role < *role {
+					return Err(forbidden("INSUFFICIENT_ROLE"));
+				}
+			},
+			Guard::Clearence(clearence) => {
+				if viewer.max_clearence() < *clearence {
+					return Err(forbidden("INSUFFICIENT_CLEARENCE"));
+				}
+			}
+		};
+		Ok(viewer)
+	}
+}
+
+/// `guard!(context, Guard::Role(UserRoleEnum::Admin))` fails the resolver with `FORBIDDEN` if not met
+macro_rules! guard {
+	($c:ident, $($g:expr),+) => {
+		{
+			$(
+				$g.check($c).await?;
+			)+
+		}
+	};
+}
@@ -3,24 +3,34 @@
 
 extern crate juniper;
 
+use std::collections::HashMap;
 use std::env;
+use std::sync::Arc;
 
-use actix_web::{App, Error, HttpMessage, HttpResponse, HttpServer, cookie, middleware, web};
-use context::Context;
+use actix_cors::Cors;
+use actix_web::{App, Error, HttpMessage, HttpResponse, HttpServer, cookie, http::header, middleware, web};
+use config::Config;
+use context::{Context, SharedState, request_language};
+use futures::StreamExt;
+use juniper::http::GraphQLBatchRequest;
 use juniper_actix::{
 	graphiql_handler as gqli_handler, graphql_handler, playground_handler as play_handler,
 };
 
+mod config;
 mod context;
 mod models;
 
 #[macro_use]
 mod common;
+#[macro_use]
+mod guard;
 mod gql;
 mod services;
 
 
-use crate::gql::{create_schema, Schema};
+use crate::services::{apiToken, upload};
+use crate::gql::{create_read_only_schema, create_schema, ReadOnlySchema, Schema};
 
 async fn graphiql_handler() -> Result<HttpResponse, Error> {
 	gqli_handler("/graphql", None).await
@@ -28,11 +38,7 @@ async fn graphiql_handler() -> Result<HttpResponse, Error> {
 async fn playground_handler() -> Result<HttpResponse, Error> {
 	play_handler("/graphql", None).await
 }
-async fn graphql(
-	req: actix_web::HttpRequest,
-	payload: actix_web::web::Payload,
-	schema: web::Data<Schema>,
-) -> Result<HttpResponse, Error> {
+fn build_context(req: &actix_web::HttpRequest, shared: &web::Data<Arc<SharedState>>) -> Context {
 	let session = req.cookie("session").map(|f| f.value().to_string());
 	let auth_header = if let Some(v) = req.headers().get("Authorization") {
 		if let Ok(v2) = v.to_str() {
@@ -43,27 +49,217 @@ async fn graphql(
 	} else {
 		None
 	};
-	let ctx = Context {
-		session,
-		auth_header
+	let header_str = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok());
+	let lang = request_language(header_str("lang"), header_str("Accept-Language"));
+	let mut ctx = Context::new(session, auth_header, lang, shared.get_ref().clone());
+	// a request limited by token scopes must not reach backend with the full rights of a cookie session
+	if apiToken::bearer_api_token(&ctx).is_some() {
+		ctx.session = None;
+	}
+	ctx
+}
+/// Cookie authenticated requests must prove they are not cross site,
+/// requests carrying a personal API token have no session and are exempt
+fn csrf_check(req: &actix_web::HttpRequest, ctx: &Context, config: &Config) -> bool {
+	if ctx.session.is_none() {
+		return true;
+	}
+	if req.headers().contains_key(config.csrf_custom_header.as_str()) {
+		return true;
+	}
+	let cookie_token = req.cookie(&config.csrf_cookie).map(|f| f.value().to_string());
+	let header_token = req.headers().get(config.csrf_header.as_str()).and_then(|v| v.to_str().ok());
+	match (cookie_token, header_token) {
+		(Some(c), Some(h)) => c.len() > 0 && c == h,
+		_ => false
+	}
+}
+/// Max size of a request body read by the gateway itself instead of juniper
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Query documents of a single or batched request
+fn request_queries(operations: &serde_json::Value) -> Vec<&str> {
+	fn query(o: &serde_json::Value) -> &str {
+		o.get("query").and_then(|q| q.as_str()).unwrap_or("")
+	}
+	match operations {
+		serde_json::Value::Array(batch) => batch.iter().map(query).collect(),
+		o => vec![query(o)]
+	}
+}
+/// Requests carrying a personal API token may only select root fields allowed by its scopes
+async fn check_token_scopes(ctx: &Context, operations: &serde_json::Value) -> Result<(), HttpResponse> {
+	for query in request_queries(operations) {
+		if let Err(e) = apiToken::checkTokenScopes_impl(ctx, query).await {
+			return Err(HttpResponse::Forbidden().json(serde_json::json!({
+				"errors": [{ "message": e.message(), "extensions": e.extensions() }]
+			})));
+		}
+	}
+	Ok(())
+}
+async fn execute_batch(schema: &Schema, ctx: &Context, operations: serde_json::Value) -> Result<HttpResponse, Error> {
+	let gql_request: GraphQLBatchRequest = serde_json::from_value(operations)?;
+	let gql_response = gql_request.execute(schema, ctx).await;
+	let body = serde_json::to_string(&gql_response)?;
+	let mut resp = if gql_response.is_ok() {
+		HttpResponse::Ok()
+	} else {
+		HttpResponse::BadRequest()
+	};
+	Ok(resp.content_type("application/json").body(body))
+}
+async fn graphql(
+	req: actix_web::HttpRequest,
+	payload: actix_web::web::Payload,
+	schema: web::Data<Schema>,
+	config: web::Data<Config>,
+	shared: web::Data<Arc<SharedState>>,
+) -> Result<HttpResponse, Error> {
+	let ctx = build_context(&req, &shared);
+	if !csrf_check(&req, &ctx, &config) {
+		return Ok(HttpResponse::Forbidden().json(serde_json::json!({
+			"errors": [{ "message": "CSRF_CHECK_FAILED" }]
+		})));
+	}
+	let is_multipart = req.headers().get(header::CONTENT_TYPE)
+		.and_then(|v| v.to_str().ok())
+		.map_or(false, |v| v.starts_with("multipart/form-data"));
+	if is_multipart {
+		return graphql_multipart(req, payload, &schema, ctx, &config).await;
+	}
+	if apiToken::bearer_api_token(&ctx).is_some() {
+		return graphql_with_token(req, payload, &schema, ctx).await;
+	}
+	graphql_handler(&schema, &ctx, req, payload).await
+}
+/// Token authenticated requests are parsed here so root fields can be checked before execution
+async fn graphql_with_token(
+	req: actix_web::HttpRequest,
+	mut payload: actix_web::web::Payload,
+	schema: &Schema,
+	ctx: Context,
+) -> Result<HttpResponse, Error> {
+	let mut body = web::BytesMut::new();
+	while let Some(chunk) = payload.next().await {
+		let chunk = chunk?;
+		if body.len() + chunk.len() > MAX_BODY_BYTES {
+			return Ok(HttpResponse::PayloadTooLarge().json(serde_json::json!({
+				"errors": [{ "message": "Request body too large" }]
+			})));
+		}
+		body.extend_from_slice(&chunk);
+	}
+	let is_graphql = req.headers().get(header::CONTENT_TYPE)
+		.and_then(|v| v.to_str().ok())
+		.map_or(false, |v| v.starts_with("application/graphql"));
+	let operations = if is_graphql {
+		serde_json::json!({ "query": String::from_utf8_lossy(&body) })
+	} else {
+		serde_json::from_slice(&body)?
+	};
+	if let Err(resp) = check_token_scopes(&ctx, &operations).await {
+		return Ok(resp);
+	}
+	execute_batch(schema, &ctx, operations).await
+}
+fn multipart_error_response(e: upload::MultipartRequestError) -> HttpResponse {
+	let mut resp = match e {
+		upload::MultipartRequestError::TooLarge | upload::MultipartRequestError::TooManyFiles => HttpResponse::PayloadTooLarge(),
+		upload::MultipartRequestError::UnsupportedMediaType(_) => HttpResponse::UnsupportedMediaType(),
+		upload::MultipartRequestError::Malformed(_) => HttpResponse::BadRequest(),
+		upload::MultipartRequestError::Backend(_) => HttpResponse::BadGateway(),
+	};
+	resp.json(serde_json::json!({
+		"errors": [{ "message": e.to_string() }]
+	}))
+}
+async fn graphql_multipart(
+	req: actix_web::HttpRequest,
+	payload: actix_web::web::Payload,
+	schema: &Schema,
+	mut ctx: Context,
+	config: &Config,
+) -> Result<HttpResponse, Error> {
+	let request = match upload::MultipartRequest::begin(req.headers(), payload, config).await {
+		Ok(r) => r,
+		Err(e) => return Ok(multipart_error_response(e))
+	};
+	// files are streamed to backend, so refuse before reading them
+	if let Err(resp) = check_token_scopes(&ctx, &request.operations).await {
+		return Ok(resp);
+	}
+	let operations = request.operations.clone();
+	let files = match request.upload_files(&ctx, config).await {
+		Ok(f) => f,
+		Err(e) => return Ok(multipart_error_response(e))
 	};
+	ctx.uploads = Arc::new(files);
+	execute_batch(schema, &ctx, operations).await
+}
+/// GET requests are served by a schema without mutations
+async fn graphql_get(
+	req: actix_web::HttpRequest,
+	payload: actix_web::web::Payload,
+	schema: web::Data<ReadOnlySchema>,
+	shared: web::Data<Arc<SharedState>>,
+) -> Result<HttpResponse, Error> {
+	let ctx = build_context(&req, &shared);
+	if apiToken::bearer_api_token(&ctx).is_some() {
+		let params = web::Query::<HashMap<String, String>>::from_query(req.query_string()).map(|q| q.into_inner()).unwrap_or_default();
+		let operations = serde_json::json!({ "query": params.get("query").cloned().unwrap_or_default() });
+		if let Err(resp) = check_token_scopes(&ctx, &operations).await {
+			return Ok(resp);
+		}
+	}
 	graphql_handler(&schema, &ctx, req, payload).await
 }
 
+fn build_cors(config: &Config) -> Cors {
+	let mut cors = Cors::default()
+		.allowed_methods(vec!["GET", "POST"])
+		.allowed_headers(vec![header::AUTHORIZATION, header::ACCEPT, header::CONTENT_TYPE])
+		.allowed_header("lang")
+		.allowed_header(config.csrf_header.as_str())
+		.allowed_header(config.csrf_custom_header.as_str())
+		.max_age(3600);
+	for h in config.cors_allowed_headers.iter() {
+		cors = cors.allowed_header(h.as_str());
+	}
+	if config.cors_allowed_origins.iter().any(|o| o == "*") {
+		cors = cors.allow_any_origin();
+	} else {
+		for o in config.cors_allowed_origins.iter() {
+			cors = cors.allowed_origin(o);
+		}
+	}
+	if config.cors_allow_credentials {
+		cors = cors.supports_credentials();
+	}
+	cors
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
 	env::set_var("RUST_LOG", "info");
 	env_logger::init();
 
+	let config = Config::from_env().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+	let shared = Arc::new(SharedState::default());
+
 	let server = HttpServer::new(move || {
 		App::new()
 			.data(create_schema())
+			.data(create_read_only_schema())
+			.data(config.clone())
+			.data(shared.clone())
 			.wrap(middleware::Compress::default())
+			.wrap(build_cors(&config))
 			.wrap(middleware::Logger::default())
 			.service(
 				web::resource("/graphql")
 					.route(web::post().to(graphql))
-					.route(web::get().to(graphql)),
+					.route(web::get().to(graphql_get)),
 			)
 			.service(web::resource("/playground").route(web::get().to(playground_handler)))
 			.service(web::resource("/graphiql").route(web::get().to(graphiql_handler)))
@@ -3,11 +3,12 @@ extern crate serde_json;
 use md5::{Md5, Digest};
 
 use juniper::{EmptyMutation, FieldResult, GraphQLObject, InputValue, Object, ParseScalarResult, RootNode, ScalarValue, Value, Variables, execute, graphql_interface, graphql_value, parser::{ParseError, ScalarToken, Spanning, Token}, serde::de};
-use std::{cell::RefMut, fmt};
+use std::{cell::RefMut, fmt, sync::Arc};
 
 use serde_derive::{Serialize, Deserialize};
 use bson::oid::ObjectId;
 use chrono::{DateTime, Utc};
+use futures::future::join_all;
 use crate::{context::Context, services::{authorDB::Author, comment::{self, Thread}, playlist::ListAdjacentVideosParameters, rating::Rating}};
 
 use crate::services::users::User;
@@ -31,6 +32,24 @@ pub struct RestResult<T> {
 	pub dataerr: Option<RestResultError>
 }
 
+/// Map backend failure reason to a stable error code, original status and reason are kept in extensions
+pub fn typed_backend_error(status: String, dataerr: Option<RestResultError>) -> juniper::FieldError {
+	let reason = dataerr.map_or("UNKNOWN".to_owned(), |f| f.reason);
+	let code = match reason.as_str() {
+		"UNAUTHORISED_OPERATION" | "USER_BLOCKED" => "FORBIDDEN",
+		"VIDEO_NOT_EXIST" | "ITEM_NOT_EXIST" | "PLAYLIST_NOT_EXIST" => "NOT_FOUND",
+		"INCORRECT_REQUEST" | "INCORRECT_REPOST_TYPE" => "INVALID_ARGUMENT",
+		_ => "BACKEND_ERROR"
+	};
+	juniper::FieldError::new(
+		code,
+		graphql_value!({
+			"status": status,
+			"reason": reason
+		}),
+	)
+}
+
 use serde::de::IntoDeserializer;
 use serde::de::Deserializer;
 
@@ -225,6 +244,14 @@ impl VideoItem {
 	pub fn part_name(&self) -> &Option<String> {
 		&self.part_name
 	}
+	/// IDs of other copies of this video
+	pub fn copy_ids(&self) -> &Vec<ObjectId> {
+		&self.copies
+	}
+	/// IDs of videos in the same series, such as reuploads and translations
+	pub fn series_ids(&self) -> &Vec<ObjectId> {
+		&self.series
+	}
 }
 
 #[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
@@ -234,6 +261,61 @@ pub struct TagCategoryItem {
 	pub value: Vec<String>
 }
 
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="Tags of a single category", Context = Context)]
+pub struct TagCategoryTags {
+	pub category: TagCategoryEnum,
+	/// Most used first
+	pub tags: Vec<TagObjectValue>
+}
+
+/// Order of categories in tagsByCategory
+const TAG_CATEGORY_ORDER: [TagCategoryEnum; 7] = [
+	TagCategoryEnum::Copyright,
+	TagCategoryEnum::Character,
+	TagCategoryEnum::Author,
+	TagCategoryEnum::General,
+	TagCategoryEnum::Meta,
+	TagCategoryEnum::Language,
+	TagCategoryEnum::Soundtrack
+];
+
+/// Group tags by category, empty categories are left out
+fn group_tags_by_category(tags: Vec<TagObjectValue>) -> Vec<TagCategoryTags> {
+	let mut tags = tags.into_iter().map(|t| {
+		let (category, count, tagid) = match &t {
+			TagObjectValue::RegularTagObject(r) => (r.category.clone(), r.count, r.tagid),
+			TagObjectValue::AuthorTagObject(a) => (a.category.clone(), a.count, a.tagid)
+		};
+		(category, count, tagid, t)
+	}).collect::<Vec<_>>();
+	tags.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)));
+	TAG_CATEGORY_ORDER.iter().map(|cat| TagCategoryTags {
+		category: cat.clone(),
+		tags: tags.iter().filter(|t| &t.0 == cat).map(|t| t.3.clone()).collect()
+	}).filter(|c| c.tags.len() > 0).collect()
+}
+
+/// Tag objects of a video or playlist, fetched at most once per object
+pub type TagObjectsMemo = Arc<futures::lock::Mutex<Option<Vec<TagObjectValue>>>>;
+
+/// Ids in `tags` which refer to tags, larger values are not tag ids
+pub fn valid_tag_ids(tags: &[i64]) -> Vec<i32> {
+	tags.iter().filter(|&n| { *n < 2_147_483_647i64 }).map(|&n| n as i32).collect::<Vec<_>>()
+}
+
+async fn memoized_tag_objects(context: &Context, memo: &TagObjectsMemo, tags: &[i64]) -> FieldResult<Vec<TagObjectValue>> {
+	let mut memo = memo.lock().await;
+	if let Some(tag_objects) = memo.as_ref() {
+		return Ok(tag_objects.clone());
+	}
+	let tag_objects = editTags::getTagObjectsBatch_impl(context, editTags::GetTagObjectsBatchParameters {
+		tagid: valid_tag_ids(tags)
+	}).await?;
+	*memo = Some(tag_objects.clone());
+	Ok(tag_objects)
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PlaylistMeta {
 	pub cover: String,
@@ -242,7 +324,10 @@ pub struct PlaylistMeta {
 	pub private: bool,
 	pub privateEdit: bool,
 	pub title: String,
-	pub views: i32
+	pub views: i32,
+	/// Playlist this one was forked from
+	#[serde(default)]
+	pub forked_from: Option<ObjectId>
 }
 
 #[juniper::graphql_object(Context = Context)]
@@ -266,6 +351,10 @@ impl PlaylistMeta {
 	pub fn privateEdit(&self) -> bool {
 		self.privateEdit
 	}
+	/// ID of playlist this one was forked from
+	pub fn forked_from_id(&self) -> Option<ObjectId> {
+		self.forked_from.clone()
+	}
 }
 
 #[derive(Clone)]
@@ -278,7 +367,8 @@ pub struct Playlist {
 	pub owner: Option<bool>,
 	pub tags: Vec<i64>,
 	pub tag_by_category: Option<Vec<TagCategoryItem>>,
-	pub comment_thread: Option<ObjectId>
+	pub comment_thread: Option<ObjectId>,
+	pub tag_objects: TagObjectsMemo
 }
 
 #[juniper::graphql_object(Context = Context)]
@@ -306,6 +396,17 @@ impl Playlist {
 	pub fn owner(&self) -> Option<bool> {
 		self.owner
 	}
+	/// Playlist this one was forked from, null if not a fork or source is no longer visible
+	pub async fn forked_from(&self, context: &Context) -> FieldResult<Option<Playlist>> {
+		Ok(match self.item.forked_from.as_ref() {
+			Some(pid) => {
+				playlist::getPlaylist_impl(context, playlist::GetPlaylistParameters {
+					pid: pid.to_string()
+				}).await.ok()
+			},
+			None => None
+		})
+	}
 	pub async fn videos(&self, context: &Context, offset: Option<i32>, limit: Option<i32>) -> FieldResult<Vec<Video>> {
 		let videos = playlist::getPlaylistContent_impl(context, playlist::GetPlaylistContentParameters {
 			offset: offset,
@@ -315,7 +416,7 @@ impl Playlist {
 		Ok(videos)
 	}
 	pub fn tag_ids(&self) -> Vec<i32> {
-		self.tags.iter().filter(|&n| { *n < 2_147_483_647i64 }).map(|&n| n as i32).collect::<Vec<_>>()
+		valid_tag_ids(&self.tags)
 	}
 	pub async fn tag_by_category(&self, context: &Context, lang: Option<String>) -> FieldResult<Vec<TagCategoryItem>> {
 		if let Some(catemap) = self.tag_by_category.clone() {
@@ -330,9 +431,11 @@ impl Playlist {
 		}
 	}
 	pub async fn tags(&self, context: &Context) -> FieldResult<Vec<TagObjectValue>> {
-		editTags::getTagObjectsBatch_impl(context, editTags::GetTagObjectsBatchParameters {
-			tagid: self.tags.iter().filter(|&n| { *n < 2_147_483_647i64 }).map(|&n| n as i32).collect::<Vec<_>>()
-		}).await
+		memoized_tag_objects(context, &self.tag_objects, &self.tags).await
+	}
+	/// Tags grouped by category, shares the tag objects fetched for `tags`
+	pub async fn tags_by_category(&self, context: &Context) -> FieldResult<Vec<TagCategoryTags>> {
+		Ok(group_tags_by_category(memoized_tag_objects(context, &self.tag_objects, &self.tags).await?))
 	}
 	pub async fn rating(&self, context: &Context) -> FieldResult<Option<Rating>> {
 		let rating = match rating::getRating_impl(context, rating::GetRatingParameters {
@@ -354,6 +457,24 @@ impl Playlist {
 			None => None
 		})
 	}
+	/// Users other than owner allowed to edit this playlist
+	pub async fn collaborators(&self, context: &Context) -> FieldResult<Vec<User>> {
+		playlistCollaborators::listCollaborators_impl(context, self._id.to_string()).await
+	}
+	/// Log of videos added, removed and moved, only visible to editors
+	pub async fn activity(&self, context: &Context, offset: Option<i32>, limit: Option<i32>) -> FieldResult<playlistCollaborators::PlaylistActivityResult> {
+		// playlists built from listings do not know if viewer can edit them
+		let editable = match self.editable {
+			Some(e) => e,
+			None => playlist::getPlaylist_impl(context, playlist::GetPlaylistParameters {
+				pid: self._id.to_string()
+			}).await?.editable == Some(true)
+		};
+		if !editable {
+			return Err(guard::forbidden("NOT_PLAYLIST_EDITOR"));
+		}
+		playlistCollaborators::listPlaylistActivity_impl(context, self._id.to_string(), offset, limit).await
+	}
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -397,7 +518,7 @@ impl PlaylistContentForVideo {
 		}).await?)
 	}
 	/// Next video
-	pub async fn next(&self, context: &Context, lang: String) -> FieldResult<Option<Video>> {
+	pub async fn next(&self, context: &Context, lang: Option<String>) -> FieldResult<Option<Video>> {
 		Ok(if let Some(vid) = &self.next {
 			let vidobj = getVideo::getVideo_impl(context, getVideo::GetVideoParameters {
 				lang: lang,
@@ -409,7 +530,7 @@ impl PlaylistContentForVideo {
 		})
 	}
 	/// Previous video
-	pub async fn prev(&self, context: &Context, lang: String) -> FieldResult<Option<Video>> {
+	pub async fn prev(&self, context: &Context, lang: Option<String>) -> FieldResult<Option<Video>> {
 		Ok(if let Some(vid) = &self.prev {
 			let vidobj = getVideo::getVideo_impl(context, getVideo::GetVideoParameters {
 				lang: lang,
@@ -422,7 +543,8 @@ impl PlaylistContentForVideo {
 	}
 }
 
-use crate::services::{authorDB, editTags, getVideo, playlist, rating, users};
+use crate::services::{authorDB, editTags, getVideo, playlist, playlistCollaborators, rating, relatedTags, tagImplication, users};
+use crate::guard;
 
 #[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
 #[graphql(description="VideoRank", Context = Context)]
@@ -443,7 +565,9 @@ pub struct Video {
 	pub tag_by_category: Option<Vec<TagCategoryItem>>,
 	pub copies: Option<Vec<Video>>,
 	pub playlists: Option<Vec<PlaylistContentForVideo>>,
-	pub comment_thread: Option<ObjectId>
+	pub comment_thread: Option<ObjectId>,
+	#[serde(skip)]
+	pub tag_objects: TagObjectsMemo
 }
 
 #[juniper::graphql_object(Context = Context)]
@@ -465,13 +589,13 @@ impl Video {
 		self.tag_count.unwrap_or_default()
 	}
 	pub fn tag_ids(&self) -> Vec<i32> {
-		self.tags.iter().filter(|&n| { *n < 2_147_483_647i64 }).map(|&n| n as i32).collect::<Vec<_>>()
+		valid_tag_ids(&self.tags)
 	}
 	#[deprecated]
 	pub fn tags_readable(&self) -> &Option<Vec<String>> {
 		&self.tags_readable
 	}
-	pub async fn tag_by_category(&self, context: &Context, lang: String) -> FieldResult<Vec<TagCategoryItem>> {
+	pub async fn tag_by_category(&self, context: &Context, lang: Option<String>) -> FieldResult<Vec<TagCategoryItem>> {
 		if let Some(catemap) = self.tag_by_category.clone() {
 			Ok(catemap)
 		} else {
@@ -485,11 +609,13 @@ impl Video {
 		}
 	}
 	pub async fn tags(&self, context: &Context) -> FieldResult<Vec<TagObjectValue>> {
-		editTags::getTagObjectsBatch_impl(context, editTags::GetTagObjectsBatchParameters {
-			tagid: self.tags.iter().filter(|&n| { *n < 2_147_483_647i64 }).map(|&n| n as i32).collect::<Vec<_>>()
-		}).await
+		memoized_tag_objects(context, &self.tag_objects, &self.tags).await
 	}
-	pub async fn copies(&self, context: &Context, lang: String) -> FieldResult<Vec<Video>> {
+	/// Tags grouped by category, shares the tag objects fetched for `tags`
+	pub async fn tags_by_category(&self, context: &Context) -> FieldResult<Vec<TagCategoryTags>> {
+		Ok(group_tags_by_category(memoized_tag_objects(context, &self.tag_objects, &self.tags).await?))
+	}
+	pub async fn copies(&self, context: &Context, lang: Option<String>) -> FieldResult<Vec<Video>> {
 		if let Some(copies) = self.copies.clone() {
 			Ok(copies)
 		} else {
@@ -500,7 +626,7 @@ impl Video {
 			Ok(vidobj.copies.unwrap())
 		}
 	}
-	pub async fn playlists(&self, context: &Context, lang: String) -> FieldResult<Vec<PlaylistContentForVideo>> {
+	pub async fn playlists(&self, context: &Context, lang: Option<String>) -> FieldResult<Vec<PlaylistContentForVideo>> {
 		if let Some(playlists) = self.playlists.clone() {
 			Ok(playlists)
 		} else {
@@ -511,6 +637,26 @@ impl Video {
 			Ok(vidobj.playlists.unwrap())
 		}
 	}
+	/// Sibling parts of a multi-part video ordered by page, only this video if it has a single part
+	pub async fn parts(&self, context: &Context) -> FieldResult<Vec<getVideo::VideoPart>> {
+		if self.item.cid.is_none() {
+			return Ok(vec![getVideo::VideoPart {
+				page: 1,
+				cid: None,
+				part_name: self.item.part_name.clone(),
+				video: self.clone()
+			}]);
+		}
+		getVideo::listVideoParts_impl(context, self._id.to_string()).await
+	}
+	/// Videos in the same series, such as reuploads and translations, deleted or hidden ones are skipped
+	pub async fn series(&self, context: &Context, lang: Option<String>) -> FieldResult<Vec<Video>> {
+		let ret = join_all(self.item.series.iter().map(|vid| getVideo::getVideo_impl(context, getVideo::GetVideoParameters {
+			lang: lang.clone(),
+			vid: vid.to_string()
+		}))).await;
+		Ok(ret.into_iter().filter_map(|v| v.ok()).collect())
+	}
 	pub async fn rating(&self, context: &Context) -> FieldResult<Option<Rating>> {
 		let rating = match rating::getRating_impl(context, rating::GetRatingParameters {
 			vid: Some(self._id.to_string()),
@@ -543,8 +689,19 @@ pub struct MultilingualMapping {
 	pub value: String
 }
 
-#[derive(GraphQLObject, Clone, Serialize, Deserialize)]
-#[graphql(description="RegularTagObject", impl = TagObjectValue, Context = Context)]
+/// Languages tried in order when a tag has no name in requested language
+pub fn default_lang_fallback() -> Vec<String> {
+	vec!["CHS".to_string(), "ENG".to_string()]
+}
+
+/// Name in `lang`, then in each language of `fallback`, then in any language
+pub fn pick_language<'a>(languages: &'a Vec<MultilingualMapping>, lang: &str, fallback: &[String]) -> Option<&'a MultilingualMapping> {
+	std::iter::once(lang).chain(fallback.iter().map(|l| l.as_str()))
+		.find_map(|l| languages.iter().find(|m| m.lang == l))
+		.or(languages.first())
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct RegularTagObject {
 	pub tagid: i32,
 	pub _id: ObjectId,
@@ -556,8 +713,7 @@ pub struct RegularTagObject {
 	pub meta: Meta
 }
 
-#[derive(GraphQLObject, Clone, Serialize, Deserialize)]
-#[graphql(description="AuthorTagObject", impl = TagObjectValue, Context = Context)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AuthorTagObject {
 	pub tagid: i32,
 	pub _id: ObjectId,
@@ -571,6 +727,102 @@ pub struct AuthorTagObject {
 	pub author_role: String
 }
 
+#[juniper::graphql_object(Context = Context, impl = TagObjectValue)]
+#[graphql(description="RegularTagObject")]
+impl RegularTagObject {
+	pub fn tagid(&self) -> i32 {
+		self.tagid
+	}
+	pub fn id(&self) -> &ObjectId {
+		&self._id
+	}
+	pub fn category(&self) -> &TagCategoryEnum {
+		&self.category
+	}
+	pub fn count(&self) -> i32 {
+		self.count
+	}
+	pub fn languages(&self) -> &Vec<MultilingualMapping> {
+		&self.languages
+	}
+	pub fn alias(&self) -> &Vec<String> {
+		&self.alias
+	}
+	pub fn is_author(&self) -> bool {
+		self.is_author
+	}
+	pub fn meta(&self) -> &Meta {
+		&self.meta
+	}
+	/// Name in `lang`, then in each language of `fallback` (default 'CHS', 'ENG'), then in any language
+	pub fn display_name(&self, context: &Context, lang: Option<String>, fallback: Option<Vec<String>>) -> String {
+		tag_display_name(context, &self.languages, &self.alias, lang, fallback)
+	}
+	/// Tags directly implied by this tag
+	pub async fn implies(&self, context: &Context) -> FieldResult<Vec<TagObjectValue>> {
+		tag_implies(context, self.tagid).await
+	}
+	/// Tags directly implying this tag
+	pub async fn implied_by(&self, context: &Context) -> FieldResult<Vec<TagObjectValue>> {
+		tag_implied_by(context, self.tagid).await
+	}
+	/// Tags most often used together with this tag, estimated from recent videos
+	pub async fn related_tags(&self, context: &Context, limit: Option<i32>) -> FieldResult<Vec<relatedTags::RelatedTag>> {
+		tag_related_tags(context, self.tagid, &self.languages, limit).await
+	}
+}
+
+#[juniper::graphql_object(Context = Context, impl = TagObjectValue)]
+#[graphql(description="AuthorTagObject")]
+impl AuthorTagObject {
+	pub fn tagid(&self) -> i32 {
+		self.tagid
+	}
+	pub fn id(&self) -> &ObjectId {
+		&self._id
+	}
+	pub fn category(&self) -> &TagCategoryEnum {
+		&self.category
+	}
+	pub fn count(&self) -> i32 {
+		self.count
+	}
+	pub fn languages(&self) -> &Vec<MultilingualMapping> {
+		&self.languages
+	}
+	pub fn alias(&self) -> &Vec<String> {
+		&self.alias
+	}
+	pub fn author(&self) -> &Option<Author> {
+		&self.author
+	}
+	pub fn is_author(&self) -> bool {
+		self.is_author
+	}
+	pub fn meta(&self) -> &Meta {
+		&self.meta
+	}
+	pub fn author_role(&self) -> &String {
+		&self.author_role
+	}
+	/// Name in `lang`, then in each language of `fallback` (default 'CHS', 'ENG'), then in any language
+	pub fn display_name(&self, context: &Context, lang: Option<String>, fallback: Option<Vec<String>>) -> String {
+		tag_display_name(context, &self.languages, &self.alias, lang, fallback)
+	}
+	/// Tags directly implied by this tag
+	pub async fn implies(&self, context: &Context) -> FieldResult<Vec<TagObjectValue>> {
+		tag_implies(context, self.tagid).await
+	}
+	/// Tags directly implying this tag
+	pub async fn implied_by(&self, context: &Context) -> FieldResult<Vec<TagObjectValue>> {
+		tag_implied_by(context, self.tagid).await
+	}
+	/// Tags most often used together with this tag, estimated from recent videos
+	pub async fn related_tags(&self, context: &Context, limit: Option<i32>) -> FieldResult<Vec<relatedTags::RelatedTag>> {
+		tag_related_tags(context, self.tagid, &self.languages, limit).await
+	}
+}
+
 #[graphql_interface(for = [RegularTagObject, AuthorTagObject], Context = Context)] // enumerating all implementers is mandatory 
 pub trait TagObject {
 	async fn id(&self) -> ObjectId;
@@ -581,6 +833,36 @@ pub trait TagObject {
 	async fn alias(&self) -> &Vec<String>;
 	async fn is_author(&self) -> bool;
 	async fn meta(&self) -> &Meta;
+	/// Name in `lang`, then in each language of `fallback` (default 'CHS', 'ENG'), then in any language
+	async fn display_name(&self, context: &Context, lang: Option<String>, fallback: Option<Vec<String>>) -> String;
+	/// Tags directly implied by this tag
+	async fn implies(&self, context: &Context) -> FieldResult<Vec<TagObjectValue>>;
+	/// Tags directly implying this tag
+	async fn implied_by(&self, context: &Context) -> FieldResult<Vec<TagObjectValue>>;
+	/// Tags most often used together with this tag, estimated from recent videos
+	async fn related_tags(&self, context: &Context, limit: Option<i32>) -> FieldResult<Vec<relatedTags::RelatedTag>>;
+}
+
+// Fields shared by RegularTagObject and AuthorTagObject, used by both the objects and their TagObject impls
+
+fn tag_display_name(context: &Context, languages: &Vec<MultilingualMapping>, alias: &Vec<String>, lang: Option<String>, fallback: Option<Vec<String>>) -> String {
+	let lang = context.lang_or_default(lang);
+	pick_language(languages, &lang, &fallback.unwrap_or_else(default_lang_fallback))
+		.map(|m| m.value.clone())
+		.or(alias.first().cloned())
+		.unwrap_or_default()
+}
+
+async fn tag_implies(context: &Context, tagid: i32) -> FieldResult<Vec<TagObjectValue>> {
+	tagImplication::listImplications_impl(context, tagid, false).await
+}
+
+async fn tag_implied_by(context: &Context, tagid: i32) -> FieldResult<Vec<TagObjectValue>> {
+	tagImplication::listImplications_impl(context, tagid, true).await
+}
+
+async fn tag_related_tags(context: &Context, tagid: i32, languages: &Vec<MultilingualMapping>, limit: Option<i32>) -> FieldResult<Vec<relatedTags::RelatedTag>> {
+	relatedTags::relatedTags_impl(context, tagid, languages, limit).await
 }
 
 #[juniper::graphql_interface]
@@ -609,6 +891,18 @@ impl TagObject for RegularTagObject {
 	async fn meta(&self) -> &Meta {
 		&self.meta
 	}
+	async fn display_name(&self, context: &Context, lang: Option<String>, fallback: Option<Vec<String>>) -> String {
+		tag_display_name(context, &self.languages, &self.alias, lang, fallback)
+	}
+	async fn implies(&self, context: &Context) -> FieldResult<Vec<TagObjectValue>> {
+		tag_implies(context, self.tagid).await
+	}
+	async fn implied_by(&self, context: &Context) -> FieldResult<Vec<TagObjectValue>> {
+		tag_implied_by(context, self.tagid).await
+	}
+	async fn related_tags(&self, context: &Context, limit: Option<i32>) -> FieldResult<Vec<relatedTags::RelatedTag>> {
+		tag_related_tags(context, self.tagid, &self.languages, limit).await
+	}
 }
 
 #[juniper::graphql_interface]
@@ -637,6 +931,18 @@ impl TagObject for AuthorTagObject {
 	async fn meta(&self) -> &Meta {
 		&self.meta
 	}
+	async fn display_name(&self, context: &Context, lang: Option<String>, fallback: Option<Vec<String>>) -> String {
+		tag_display_name(context, &self.languages, &self.alias, lang, fallback)
+	}
+	async fn implies(&self, context: &Context) -> FieldResult<Vec<TagObjectValue>> {
+		tag_implies(context, self.tagid).await
+	}
+	async fn implied_by(&self, context: &Context) -> FieldResult<Vec<TagObjectValue>> {
+		tag_implied_by(context, self.tagid).await
+	}
+	async fn related_tags(&self, context: &Context, limit: Option<i32>) -> FieldResult<Vec<relatedTags::RelatedTag>> {
+		tag_related_tags(context, self.tagid, &self.languages, limit).await
+	}
 }
 
 impl Clone for TagObjectValue {
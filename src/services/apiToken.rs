@@ -0,0 +1,545 @@
+
+use juniper::graphql_value;
+
+
+use juniper::FieldResult;
+use serde_json::json;
+
+use crate::common::*;
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Serialize, Deserialize};
+use bson::oid::ObjectId;
+use crate::models::{Meta, Error, RestResult};
+use crate::context::Context;
+
+/// Personal API tokens are sent as `Authorization: Bearer pvt_...`
+pub const API_TOKEN_PREFIX: &str = "pvt_";
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiTokenScopeEnum {
+	/// Read user specific data such as subscriptions and notifications
+	Read,
+	/// Edit tags and clearence of videos
+	TagEdit,
+	/// Post videos
+	Post,
+	/// Post, edit and delete comments, send direct messages
+	Comment,
+	/// Create, edit and delete playlists
+	Playlist,
+	/// Rate videos and playlists
+	Rate
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+	pub _id: ObjectId,
+	pub name: String,
+	pub scopes: Vec<ApiTokenScopeEnum>,
+	pub expires_at: bson::DateTime,
+	pub last_used_at: Option<bson::DateTime>,
+	pub meta: Meta
+}
+
+#[juniper::graphql_object(Context = Context)]
+#[graphql(description="Personal API token, the token itself is only shown once when created")]
+impl ApiToken {
+	pub fn id(&self) -> ObjectId {
+		self._id.clone()
+	}
+	pub fn name(&self) -> &String {
+		&self.name
+	}
+	pub fn scopes(&self) -> &Vec<ApiTokenScopeEnum> {
+		&self.scopes
+	}
+	pub fn expires_at(&self) -> &bson::DateTime {
+		&self.expires_at
+	}
+	pub fn last_used_at(&self) -> Option<&bson::DateTime> {
+		self.last_used_at.as_ref()
+	}
+	/// If this token has expired
+	pub fn expired(&self) -> bool {
+		self.expires_at.0 < Utc::now()
+	}
+	pub fn meta(&self) -> &Meta {
+		&self.meta
+	}
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="createApiToken required parameters", Context = Context)]
+pub struct CreateApiTokenParameters {
+	/// Name of this token
+	pub name: String,
+	/// Scopes granted to this token
+	pub scopes: Vec<ApiTokenScopeEnum>,
+	/// Days until this token expires, default 30, at most 365
+	pub expires_in_days: Option<i32>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CreateApiTokenResult {
+	pub token: String,
+	pub token_obj: ApiToken
+}
+
+#[juniper::graphql_object(Context = Context)]
+#[graphql(description="createApiToken result")]
+impl CreateApiTokenResult {
+	/// The token, save it now since it can not be retrieved again
+	pub fn token(&self) -> &String {
+		&self.token
+	}
+	pub fn info(&self) -> &ApiToken {
+		&self.token_obj
+	}
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ListApiTokensResult {
+	pub tokens: Vec<ApiToken>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VerifyApiTokenResult {
+	pub token_obj: ApiToken
+}
+
+/// Returns the personal API token carried in `Authorization` header if any
+pub fn bearer_api_token(context: &Context) -> Option<&str> {
+	let auth = context.auth_header.as_ref()?;
+	let token = auth.strip_prefix("Bearer ")?.trim();
+	if token.starts_with(API_TOKEN_PREFIX) {
+		Some(token)
+	} else {
+		None
+	}
+}
+
+/// Resolve the personal API token used by this request, cached in context
+pub async fn resolveApiToken_impl(context: &Context) -> FieldResult<Option<ApiToken>> {
+	if let Some(cached) = context.api_token.lock().unwrap().as_ref() {
+		return Ok(cached.clone());
+	}
+	let token = match bearer_api_token(context) {
+		Some(t) => t.to_string(),
+		None => {
+			*context.api_token.lock().unwrap() = Some(None);
+			return Ok(None);
+		}
+	};
+	let req = json!({
+		"token": token
+	});
+	let result = postJSON!(VerifyApiTokenResult, format!("{}/user/tokens/verify.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		let token_obj = result.data.unwrap().token_obj;
+		*context.api_token.lock().unwrap() = Some(Some(token_obj.clone()));
+		Ok(Some(token_obj))
+	} else {
+		Err(
+			juniper::FieldError::new(
+				"INVALID_TOKEN",
+				graphql_value!({
+					"Personal API token is invalid or revoked"
+				}),
+			)
+		)
+	}
+}
+
+/// What a personal API token needs to select a root field
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldScope {
+	/// Any valid token
+	Public,
+	/// Token must carry this scope
+	Token(ApiTokenScopeEnum),
+	/// Only allowed from a logged in session
+	SessionOnly
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OperationKind {
+	Query,
+	Mutation,
+	Subscription
+}
+
+/// Scope of every root field, fields not listed here are refused for personal API tokens
+pub fn root_field_scope(kind: OperationKind, field: &str) -> Option<FieldScope> {
+	use OperationKind::*;
+	use ApiTokenScopeEnum::*;
+	Some(match (kind, field) {
+		(_, "__typename") | (_, "apiVersion") | (_, "serverDate") => FieldScope::Public,
+		(Query, "__schema") | (Query, "__type") => FieldScope::Public,
+		(Query, "listVideo") | (Query, "explainVideoQuery") | (Query, "getVideo") | (Query, "getRelatedVideo") |
+		(Query, "search") | (Query, "resolveVideoUrl") | (Query, "checkVideoExists") |
+		(Query, "getTagObjects") | (Query, "listTagObjects") | (Query, "getPopularTags") | (Query, "autocompleteTags") |
+		(Query, "getAuthor") | (Query, "getPlaylist") | (Query, "listPlaylist") | (Query, "listAdjacentVideos") |
+		(Query, "forkProgress") | (Query, "exportPlaylist") | (Query, "getUser") | (Query, "viewer") | (Query, "whoami") |
+		(Query, "getRating") | (Query, "listSubscriptions") | (Query, "listSubscriptionVideos") | (Query, "listSubscriptionVideosRandomized") |
+		(Query, "listNotifications") | (Query, "listUnreadNotificationsCount") | (Query, "getThread") |
+		(Query, "getStats") | (Query, "getLeaderboard") | (Query, "getRawTagHistory") => FieldScope::Token(Read),
+		(Query, "listApiTokens") => FieldScope::SessionOnly,
+		(Mutation, "postVideo") | (Mutation, "batchPostVideo") | (Mutation, "postVideoAllParts") |
+		(Mutation, "importPlaylist") => FieldScope::Token(Post),
+		(Mutation, "editVideoTags") | (Mutation, "editVideoTagsExpanded") | (Mutation, "editVideoTagIds") | (Mutation, "setVideoClearence") |
+		(Mutation, "deleteVideo") | (Mutation, "refreshVideoMetadata") | (Mutation, "setRepostType") |
+		(Mutation, "linkCopies") | (Mutation, "unlinkCopy") | (Mutation, "syncTagsAcrossCopies") |
+		(Mutation, "associateWithPvUser") | (Mutation, "disassociateWithPvUser") |
+		(Mutation, "addTag") | (Mutation, "removeTag") | (Mutation, "renameTag") | (Mutation, "addAlias") |
+		(Mutation, "removeAlias") | (Mutation, "renameAlias") | (Mutation, "transferCategory") |
+		(Mutation, "addTagLanguage") | (Mutation, "mergeTag") | (Mutation, "addTagImplication") | (Mutation, "removeTagImplication") |
+		(Mutation, "editPlaylistTags") | (Mutation, "editPlaylistTagIds") => FieldScope::Token(TagEdit),
+		(Mutation, "markAsRead") | (Mutation, "addSubscription") | (Mutation, "updateSubscription") |
+		(Mutation, "removeSubscription") => FieldScope::Token(Read),
+		(Mutation, "sendDM") | (Mutation, "postComment") | (Mutation, "postReply") | (Mutation, "editComment") |
+		(Mutation, "hideComment") | (Mutation, "delComment") | (Mutation, "pinComment") => FieldScope::Token(Comment),
+		(Mutation, "setPlaylistCover") | (Mutation, "createPlaylist") | (Mutation, "updatePlaylist") |
+		(Mutation, "deletePlaylist") | (Mutation, "addVideoToPlaylist") | (Mutation, "removeVideoFromPlaylist") |
+		(Mutation, "movePlaylistVideo") | (Mutation, "addToFavourites") | (Mutation, "removeFromFavourites") |
+		(Mutation, "forkPlaylist") | (Mutation, "resumeFork") | (Mutation, "addPlaylistCollaborator") |
+		(Mutation, "removePlaylistCollaborator") => FieldScope::Token(Playlist),
+		(Mutation, "rateVideo") | (Mutation, "ratePlaylist") | (Mutation, "removeVideoRating") |
+		(Mutation, "removePlaylistRating") => FieldScope::Token(Rate),
+		(Mutation, "createApiToken") | (Mutation, "revokeApiToken") | (Mutation, "uploadAvatar") => FieldScope::SessionOnly,
+		_ => return None
+	})
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum GqlToken {
+	Punct(char),
+	Spread,
+	Name(String),
+	/// String or number literal
+	Value
+}
+
+fn gql_tokens(query: &str) -> Option<Vec<GqlToken>> {
+	let chars = query.chars().collect::<Vec<_>>();
+	let mut tokens = vec![];
+	let mut i = 0;
+	while i < chars.len() {
+		let c = chars[i];
+		if c.is_whitespace() || c == ',' || c == '\u{feff}' {
+			i += 1;
+		} else if c == '#' {
+			while i < chars.len() && chars[i] != '\n' && chars[i] != '\r' {
+				i += 1;
+			}
+		} else if c == '"' {
+			if chars.get(i + 1) == Some(&'"') && chars.get(i + 2) == Some(&'"') {
+				i += 3;
+				loop {
+					match chars.get(i) {
+						None => return None,
+						Some('\\') if chars[i + 1..].starts_with(&['"', '"', '"']) => i += 4,
+						Some('"') if chars[i..].starts_with(&['"', '"', '"']) => { i += 3; break; },
+						Some(_) => i += 1
+					}
+				}
+			} else {
+				i += 1;
+				loop {
+					match chars.get(i) {
+						None | Some('\n') => return None,
+						Some('\\') => i += 2,
+						Some('"') => { i += 1; break; },
+						Some(_) => i += 1
+					}
+				}
+			}
+			tokens.push(GqlToken::Value);
+		} else if c == '.' {
+			if !chars[i..].starts_with(&['.', '.', '.']) {
+				return None;
+			}
+			tokens.push(GqlToken::Spread);
+			i += 3;
+		} else if c.is_ascii_alphabetic() || c == '_' {
+			let start = i;
+			while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+				i += 1;
+			}
+			tokens.push(GqlToken::Name(chars[start..i].iter().collect()));
+		} else if c.is_ascii_digit() || c == '-' {
+			while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.' || chars[i] == '+' || chars[i] == '-') {
+				i += 1;
+			}
+			tokens.push(GqlToken::Value);
+		} else if "{}()[]:@$!=|&".contains(c) {
+			tokens.push(GqlToken::Punct(c));
+			i += 1;
+		} else {
+			return None;
+		}
+	}
+	Some(tokens)
+}
+
+/// Skip a balanced `open ... close` group starting at `tokens[*i]`
+fn skip_group(tokens: &[GqlToken], i: &mut usize, open: char, close: char) -> Option<()> {
+	if tokens.get(*i) != Some(&GqlToken::Punct(open)) {
+		return None;
+	}
+	let mut depth = 0;
+	loop {
+		match tokens.get(*i)? {
+			GqlToken::Punct(c) if *c == open => depth += 1,
+			GqlToken::Punct(c) if *c == close => depth -= 1,
+			_ => {}
+		}
+		*i += 1;
+		if depth == 0 {
+			return Some(());
+		}
+	}
+}
+
+fn skip_directives(tokens: &[GqlToken], i: &mut usize) -> Option<()> {
+	while tokens.get(*i) == Some(&GqlToken::Punct('@')) {
+		*i += 1;
+		match tokens.get(*i)? {
+			GqlToken::Name(_) => *i += 1,
+			_ => return None
+		}
+		if tokens.get(*i) == Some(&GqlToken::Punct('(')) {
+			skip_group(tokens, i, '(', ')')?;
+		}
+	}
+	Some(())
+}
+
+/// Root fields selected by every operation in a GraphQL document,
+/// None if the document can not be read or selects root fields through fragments
+pub fn root_fields(query: &str) -> Option<Vec<(OperationKind, String)>> {
+	let tokens = gql_tokens(query)?;
+	let mut fields = vec![];
+	let mut i = 0;
+	while i < tokens.len() {
+		let kind = match &tokens[i] {
+			GqlToken::Punct('{') => OperationKind::Query,
+			GqlToken::Name(n) if n == "fragment" => {
+				while tokens.get(i)? != &GqlToken::Punct('{') {
+					i += 1;
+				}
+				skip_group(&tokens, &mut i, '{', '}')?;
+				continue;
+			},
+			GqlToken::Name(n) => {
+				let kind = match n.as_str() {
+					"query" => OperationKind::Query,
+					"mutation" => OperationKind::Mutation,
+					"subscription" => OperationKind::Subscription,
+					_ => return None
+				};
+				i += 1;
+				if let Some(GqlToken::Name(_)) = tokens.get(i) {
+					i += 1;
+				}
+				if tokens.get(i) == Some(&GqlToken::Punct('(')) {
+					skip_group(&tokens, &mut i, '(', ')')?;
+				}
+				skip_directives(&tokens, &mut i)?;
+				kind
+			},
+			_ => return None
+		};
+		if tokens.get(i) != Some(&GqlToken::Punct('{')) {
+			return None;
+		}
+		i += 1;
+		loop {
+			match tokens.get(i)? {
+				GqlToken::Punct('}') => {
+					i += 1;
+					break;
+				},
+				GqlToken::Name(name) => {
+					let mut field = name.clone();
+					i += 1;
+					if tokens.get(i) == Some(&GqlToken::Punct(':')) {
+						i += 1;
+						match tokens.get(i)? {
+							GqlToken::Name(n) => field = n.clone(),
+							_ => return None
+						}
+						i += 1;
+					}
+					if tokens.get(i) == Some(&GqlToken::Punct('(')) {
+						skip_group(&tokens, &mut i, '(', ')')?;
+					}
+					skip_directives(&tokens, &mut i)?;
+					if tokens.get(i) == Some(&GqlToken::Punct('{')) {
+						skip_group(&tokens, &mut i, '{', '}')?;
+					}
+					fields.push((kind, field));
+				},
+				_ => return None
+			}
+		}
+	}
+	Some(fields)
+}
+
+fn scope_error(reason: &str, field: &str) -> juniper::FieldError {
+	juniper::FieldError::new(
+		"INSUFFICIENT_SCOPE",
+		graphql_value!({
+			"reason": reason,
+			"field": field
+		}),
+	)
+}
+
+/// Requests authenticated by session cookie always pass, requests carrying a personal API token
+/// are refused unless every root field of `query` is allowed by the token's scopes
+pub async fn checkTokenScopes_impl(context: &Context, query: &str) -> FieldResult<()> {
+	if bearer_api_token(context).is_none() {
+		return Ok(());
+	}
+	let fields = match root_fields(query) {
+		Some(f) => f,
+		None => return Err(scope_error("Query can not be checked against token scopes, select root fields without fragments", ""))
+	};
+	let token = match resolveApiToken_impl(context).await? {
+		Some(t) => t,
+		None => return Err(scope_error("Personal API token is invalid or revoked", ""))
+	};
+	if token.expires_at.0 < Utc::now() {
+		return Err(
+			juniper::FieldError::new(
+				"TOKEN_EXPIRED",
+				graphql_value!({
+					"Personal API token has expired"
+				}),
+			)
+		);
+	}
+	for (kind, field) in fields.iter() {
+		match root_field_scope(*kind, field) {
+			Some(FieldScope::Public) => {},
+			Some(FieldScope::Token(scope)) if token.scopes.contains(&scope) => {},
+			Some(FieldScope::Token(_)) => return Err(scope_error("Personal API token does not have the required scope", field)),
+			Some(FieldScope::SessionOnly) => return Err(scope_error("Only allowed from a logged in session", field)),
+			None => return Err(scope_error("Not allowed with personal API tokens", field))
+		}
+	}
+	Ok(())
+}
+
+#[test]
+fn root_fields_of_query() {
+	use OperationKind::*;
+	let f = root_fields;
+	assert_eq!(f("{ getVideo(para: {vid: \"}\"}) { item { title } } }"), Some(vec![(Query, "getVideo".to_string())]));
+	assert_eq!(f("mutation M($a: Int = 1) @x { r: removeTag(para: $a) @skip(if: false) # }\n addTag }"),
+		Some(vec![(Mutation, "removeTag".to_string()), (Mutation, "addTag".to_string())]));
+	assert_eq!(f("fragment F on Video { id } query { listVideo { videos { ...F } } } mutation { postVideo }"),
+		Some(vec![(Query, "listVideo".to_string()), (Mutation, "postVideo".to_string())]));
+	assert_eq!(f("mutation { ...F }"), None);
+	assert_eq!(f("mutation { ... on Mutation { addTag } }"), None);
+	assert_eq!(f("{ a(s: \"\"\"x \\\"\"\" } \"\"\") }"), Some(vec![(Query, "a".to_string())]));
+	assert_eq!(f("{ a(s: \"unterminated) }"), None);
+}
+
+/// Token management is only allowed from a logged in session, tokens can not mint tokens
+pub fn require_session(context: &Context) -> FieldResult<()> {
+	if bearer_api_token(context).is_some() {
+		return Err(
+			juniper::FieldError::new(
+				"INSUFFICIENT_SCOPE",
+				graphql_value!({
+					"Personal API tokens can not be used to manage tokens"
+				}),
+			)
+		);
+	}
+	Ok(())
+}
+
+pub async fn createApiToken_impl(context: &Context, para: CreateApiTokenParameters) -> FieldResult<CreateApiTokenResult> {
+	require_session(context)?;
+	if para.scopes.is_empty() {
+		return Err(
+			juniper::FieldError::new(
+				"INCORRECT_REQUEST",
+				graphql_value!({
+					"At least one scope must be set"
+				}),
+			)
+		);
+	}
+	let days = para.expires_in_days.unwrap_or(30);
+	if days < 1 || days > 365 {
+		return Err(
+			juniper::FieldError::new(
+				"INCORRECT_REQUEST",
+				graphql_value!({
+					"expires_in_days must be between 1 and 365"
+				}),
+			)
+		);
+	}
+	let req = json!({
+		"name": para.name,
+		"scopes": para.scopes,
+		"expires_in_days": days
+	});
+	let result = postJSON!(CreateApiTokenResult, format!("{}/user/tokens/create.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		Ok(result.data.unwrap())
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn listApiTokens_impl(context: &Context) -> FieldResult<Vec<ApiToken>> {
+	require_session(context)?;
+	let result = postJSON!(ListApiTokensResult, format!("{}/user/tokens/list.do", BACKEND_URL), EmptyJSON::new(), context);
+	if result.status == "SUCCEED" {
+		Ok(result.data.unwrap().tokens)
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn revokeApiToken_impl(context: &Context, token_id: String) -> FieldResult<bool> {
+	require_session(context)?;
+	let req = json!({
+		"token_id": token_id
+	});
+	let result = postJSON!(EmptyJSON, format!("{}/user/tokens/revoke.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		Ok(true)
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
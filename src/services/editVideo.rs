@@ -3,6 +3,7 @@ use juniper::graphql_value;
 
 
 use juniper::{FieldResult, ScalarValue};
+use serde_json::json;
 
 use crate::common::*;
 use crate::services::authorDB;
@@ -10,10 +11,11 @@ use crate::services::authorDB;
 use chrono::{DateTime, Utc};
 use serde_derive::{Serialize, Deserialize};
 use bson::oid::ObjectId;
+use futures::future::join_all;
 use std::convert::{TryFrom, TryInto};
 use crate::models::*;
 use crate::context::Context;
-use super::editTags;
+use super::{editTags, getVideo, tagImplication};
 
 #[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
 #[graphql(description="editVideoTags required parameters", Context = Context)]
@@ -30,6 +32,15 @@ pub struct EditVideoTagsParameters {
 	pub user_language: Option<String>,
 }
 
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="editVideoTagsExpanded result", Context = Context)]
+pub struct EditVideoTagsResult {
+	/// All tags of video after editing
+	pub tags: Vec<TagObjectValue>,
+	/// Tags added because they are implied by other tags
+	pub implied_tags: Vec<TagObjectValue>
+}
+
 #[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
 #[graphql(description="editVideoTagIds required parameters", Context = Context)]
 pub struct EditVideoTagIdsParameters {
@@ -64,13 +75,11 @@ pub struct SetVideoClearenceRespObject {
 	pub clearence: i32
 }
 
-pub async fn editVideoTags_impl(context: &Context, para: EditVideoTagsParameters) -> FieldResult<Vec<TagObjectValue>> {
+/// Edit tags of a video in backend, returns resulting tag IDs
+async fn postEditVideoTags(context: &Context, para: &EditVideoTagsParameters) -> FieldResult<Vec<i32>> {
 	let result = postJSON!(EditVideoTagsRespObject, format!("{}/videos/edittags.do", BACKEND_URL), para, context);
 	if result.status == "SUCCEED" {
-		let tagids = result.data.unwrap().tagids;
-		editTags::getTagObjectsBatch_impl(context, editTags::GetTagObjectsBatchParameters {
-			tagid: tagids
-		}).await
+		Ok(result.data.unwrap().tagids)
 	} else {
 		Err(
 			juniper::FieldError::new(
@@ -83,6 +92,44 @@ pub async fn editVideoTags_impl(context: &Context, para: EditVideoTagsParameters
 	}
 }
 
+pub async fn editVideoTags_impl(context: &Context, para: EditVideoTagsParameters) -> FieldResult<Vec<TagObjectValue>> {
+	let tagids = postEditVideoTags(context, &para).await?;
+	editTags::getTagObjectsBatch_impl(context, editTags::GetTagObjectsBatchParameters {
+		tagid: tagids
+	}).await
+}
+
+/// Edit tags like `editVideoTags`, then append tags implied by the resulting tags unless tags are removed
+pub async fn editVideoTagsExpanded_impl(context: &Context, para: EditVideoTagsParameters) -> FieldResult<EditVideoTagsResult> {
+	let tagids = postEditVideoTags(context, &para).await?;
+	let implied = if para.edit_behaviour == "remove" {
+		vec![]
+	} else {
+		tagImplication::expandImplications(context, &tagids).await?
+	};
+	let tags = if implied.is_empty() {
+		editTags::getTagObjectsBatch_impl(context, editTags::GetTagObjectsBatchParameters {
+			tagid: tagids
+		}).await?
+	} else {
+		editVideoTagIds_impl(context, EditVideoTagIdsParameters {
+			video_id: para.video_id.clone(),
+			tags: implied.clone(),
+			edit_behaviour: "append".to_string(),
+			not_found_behaviour: None,
+			user_language: para.user_language.clone()
+		}).await?
+	};
+	let implied_tags = tags.iter().filter(|t| implied.contains(&match t {
+		TagObjectValue::RegularTagObject(r) => r.tagid,
+		TagObjectValue::AuthorTagObject(a) => a.tagid
+	})).cloned().collect();
+	Ok(EditVideoTagsResult {
+		tags,
+		implied_tags
+	})
+}
+
 pub async fn editVideoTagIds_impl(context: &Context, para: EditVideoTagIdsParameters) -> FieldResult<Vec<TagObjectValue>> {
 	let result = postJSON!(EditVideoTagsRespObject, format!("{}/videos/edittagids.do", BACKEND_URL), para, context);
 	if result.status == "SUCCEED" {
@@ -118,3 +165,142 @@ pub async fn setVideoClearenceVideo_impl(context: &Context, para: SetVideoCleare
 	}
 }
 
+
+/// Valid values of repost_type
+pub const REPOST_TYPES: [&str; 7] = ["official", "official_repost", "authorized_translation", "authorized_repost", "translation", "repost", "unknown"];
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="required parameters for operations on a single video", Context = Context)]
+pub struct VideoOpParameters {
+	/// Video ID
+	pub vid: String,
+	/// Language used for returned video, default to request language
+	pub lang: Option<String>
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="setRepostType required parameters", Context = Context)]
+pub struct SetRepostTypeParameters {
+	/// Video ID
+	pub vid: String,
+	/// Type of repost, one of 'official', 'official_repost', 'authorized_translation', 'authorized_repost', 'translation', 'repost', 'unknown'
+	pub repost_type: String,
+	/// Language used for returned video, default to request language
+	pub lang: Option<String>
+}
+
+async fn fetchVideo(context: &Context, vid: String, lang: Option<String>) -> FieldResult<Video> {
+	getVideo::getVideo_impl(context, getVideo::GetVideoParameters {
+		vid: vid,
+		lang: lang
+	}).await
+}
+
+/// Returns the video as it was right before deletion
+pub async fn deleteVideo_impl(context: &Context, para: VideoOpParameters) -> FieldResult<Video> {
+	let video = fetchVideo(context, para.vid.clone(), para.lang).await?;
+	let req = json!({
+		"vid": para.vid
+	});
+	let result = postJSON!(EmptyJSON, format!("{}/videos/delete.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		Ok(video)
+	} else {
+		Err(typed_backend_error(result.status, result.dataerr))
+	}
+}
+
+/// Re-crawl title, desc, thumbnail and views from source site
+pub async fn refreshVideoMetadata_impl(context: &Context, para: VideoOpParameters) -> FieldResult<Video> {
+	let req = json!({
+		"vid": para.vid
+	});
+	let result = postJSON!(EmptyJSON, format!("{}/videos/refresh.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		fetchVideo(context, para.vid, para.lang).await
+	} else {
+		Err(typed_backend_error(result.status, result.dataerr))
+	}
+}
+
+pub async fn setRepostType_impl(context: &Context, para: SetRepostTypeParameters) -> FieldResult<Video> {
+	if !REPOST_TYPES.contains(&para.repost_type.as_str()) {
+		return Err(
+			juniper::FieldError::new(
+				"INVALID_ARGUMENT",
+				graphql_value!({
+					"reason": "INCORRECT_REPOST_TYPE"
+				}),
+			)
+		);
+	}
+	let req = json!({
+		"vid": para.vid,
+		"repost_type": para.repost_type
+	});
+	let result = postJSON!(EmptyJSON, format!("{}/videos/set_repost_type.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		fetchVideo(context, para.vid, para.lang).await
+	} else {
+		Err(typed_backend_error(result.status, result.dataerr))
+	}
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="linkCopies required parameters", Context = Context)]
+pub struct LinkCopiesParameters {
+	/// IDs of videos to be marked as copies of each other
+	pub vids: Vec<String>,
+	/// Language used for returned videos, default to request language
+	pub lang: Option<String>
+}
+
+/// Returns linked videos, ones that can not be fetched after linking are skipped
+pub async fn linkCopies_impl(context: &Context, para: LinkCopiesParameters) -> FieldResult<Vec<Video>> {
+	if para.vids.len() < 2 {
+		return Err(
+			juniper::FieldError::new(
+				"INVALID_ARGUMENT",
+				graphql_value!({
+					"reason": "At least two videos must be given"
+				}),
+			)
+		);
+	}
+	let req = json!({
+		"vids": para.vids
+	});
+	let result = postJSON!(EmptyJSON, format!("{}/videos/link_copies.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		let ret = join_all(para.vids.iter().map(|vid| fetchVideo(context, vid.clone(), para.lang.clone()))).await;
+		Ok(ret.into_iter().filter_map(|v| v.ok()).collect())
+	} else {
+		Err(typed_backend_error(result.status, result.dataerr))
+	}
+}
+
+/// Remove video from its copy group
+pub async fn unlinkCopy_impl(context: &Context, para: VideoOpParameters) -> FieldResult<Video> {
+	let req = json!({
+		"vid": para.vid
+	});
+	let result = postJSON!(EmptyJSON, format!("{}/videos/clear_copy.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		fetchVideo(context, para.vid, para.lang).await
+	} else {
+		Err(typed_backend_error(result.status, result.dataerr))
+	}
+}
+
+/// Copy tags of this video to all its copies
+pub async fn syncTagsAcrossCopies_impl(context: &Context, para: VideoOpParameters) -> FieldResult<Video> {
+	let req = json!({
+		"src": para.vid
+	});
+	let result = postJSON!(EmptyJSON, format!("{}/videos/broadcast_tags.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		fetchVideo(context, para.vid, para.lang).await
+	} else {
+		Err(typed_backend_error(result.status, result.dataerr))
+	}
+}
@@ -0,0 +1,393 @@
+
+use juniper::FieldResult;
+
+use std::collections::HashMap;
+use futures::future::join_all;
+use crate::models::{TagObjectValue, MultilingualMapping, default_lang_fallback, pick_language};
+use crate::context::Context;
+use crate::services::editTags;
+use crate::services::videoQuery::{escape_term, query_keyword};
+
+/// Max num of suggestions returned for each unknown term
+const MAX_SUGGESTIONS: i32 = 5;
+/// Max num of distinct terms resolved in one query
+const MAX_RESOLVED_TERMS: usize = 32;
+/// Filters understood by backend query language
+const FILTER_KEYS: [&str; 3] = ["site", "date", "clearence"];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+	LParen,
+	RParen,
+	Keyword(String),
+	/// (text, was quoted)
+	Word(String, bool)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Ast {
+	And(Vec<Ast>, usize),
+	Or(Vec<Ast>, usize),
+	Not(Box<Ast>, usize),
+	Term(String, usize),
+	Filter(String, String, usize)
+}
+
+#[derive(juniper::GraphQLObject, Clone, Debug, PartialEq)]
+#[graphql(description="Syntax error in a tag query", Context = Context)]
+pub struct QuerySyntaxError {
+	pub message: String,
+	/// Char position in query (start from 0)
+	pub position: i32
+}
+
+fn syntax_error(message: &str, position: usize) -> QuerySyntaxError {
+	QuerySyntaxError {
+		message: message.to_string(),
+		position: position as i32
+	}
+}
+
+fn tokenize(query: &str) -> Result<Vec<(Token, usize)>, QuerySyntaxError> {
+	let chars = query.chars().collect::<Vec<_>>();
+	let mut tokens = vec![];
+	let mut i = 0;
+	while i < chars.len() {
+		let c = chars[i];
+		if c.is_whitespace() {
+			i += 1;
+		} else if c == '(' {
+			tokens.push((Token::LParen, i));
+			i += 1;
+		} else if c == ')' {
+			tokens.push((Token::RParen, i));
+			i += 1;
+		} else {
+			let start = i;
+			let mut text = String::new();
+			let mut quoted = false;
+			while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '(' && chars[i] != ')' {
+				if chars[i] == '"' {
+					quoted = true;
+					let quote_start = i;
+					i += 1;
+					loop {
+						match chars.get(i) {
+							None => return Err(syntax_error("Unterminated quote", quote_start)),
+							Some('"') => { i += 1; break; },
+							Some('\\') => match chars.get(i + 1) {
+								Some(&e) => { text.push(e); i += 2; },
+								None => return Err(syntax_error("Unterminated quote", quote_start))
+							},
+							Some(&o) => { text.push(o); i += 1; }
+						}
+					}
+				} else {
+					text.push(chars[i]);
+					i += 1;
+				}
+			}
+			match query_keyword(&text) {
+				Some(k) if !quoted => tokens.push((Token::Keyword(k.to_string()), start)),
+				_ => tokens.push((Token::Word(text, quoted), start))
+			}
+		}
+	}
+	Ok(tokens)
+}
+
+struct Parser {
+	tokens: Vec<(Token, usize)>,
+	pos: usize,
+	end: usize
+}
+
+impl Parser {
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.pos).map(|(t, _)| t)
+	}
+	fn offset(&self) -> usize {
+		self.tokens.get(self.pos).map_or(self.end, |(_, p)| *p)
+	}
+	fn is_keyword(&self, kw: &str) -> bool {
+		matches!(self.peek(), Some(Token::Keyword(k)) if k == kw)
+	}
+	fn parse_or(&mut self) -> Result<Ast, QuerySyntaxError> {
+		let start = self.offset();
+		let mut items = vec![self.parse_and()?];
+		while self.is_keyword("OR") {
+			self.pos += 1;
+			items.push(self.parse_and()?);
+		}
+		Ok(if items.len() == 1 { items.pop().unwrap() } else { Ast::Or(items, start) })
+	}
+	fn parse_and(&mut self) -> Result<Ast, QuerySyntaxError> {
+		let start = self.offset();
+		let mut items = vec![self.parse_unary()?];
+		loop {
+			if self.is_keyword("AND") {
+				self.pos += 1;
+			} else if self.peek().is_none() || self.is_keyword("OR") || self.peek() == Some(&Token::RParen) {
+				break;
+			}
+			items.push(self.parse_unary()?);
+		}
+		Ok(if items.len() == 1 { items.pop().unwrap() } else { Ast::And(items, start) })
+	}
+	fn parse_unary(&mut self) -> Result<Ast, QuerySyntaxError> {
+		let start = self.offset();
+		match self.peek().cloned() {
+			None => Err(syntax_error("Unexpected end of query", start)),
+			Some(Token::RParen) => Err(syntax_error("Unexpected ')'", start)),
+			Some(Token::LParen) => {
+				self.pos += 1;
+				let inner = self.parse_or()?;
+				if self.peek() != Some(&Token::RParen) {
+					return Err(syntax_error("Missing ')'", self.offset()));
+				}
+				self.pos += 1;
+				Ok(inner)
+			},
+			Some(Token::Keyword(k)) if k == "NOT" => {
+				self.pos += 1;
+				Ok(Ast::Not(Box::new(self.parse_unary()?), start))
+			},
+			Some(Token::Keyword(k)) if k == "ANY" => {
+				self.pos += 1;
+				if self.peek() != Some(&Token::LParen) {
+					return Err(syntax_error("Expected '(' after ANY", self.offset()));
+				}
+				self.pos += 1;
+				let mut items = vec![];
+				while let Some(Token::Word(_, _)) = self.peek() {
+					items.push(self.parse_unary()?);
+				}
+				if self.peek() != Some(&Token::RParen) {
+					return Err(syntax_error("ANY only accepts tags", self.offset()));
+				}
+				self.pos += 1;
+				if items.len() == 0 {
+					return Err(syntax_error("ANY requires at least one tag", start));
+				}
+				Ok(if items.len() == 1 { items.pop().unwrap() } else { Ast::Or(items, start) })
+			},
+			Some(Token::Keyword(k)) => Err(syntax_error(&format!("Unexpected '{}'", k), start)),
+			Some(Token::Word(text, quoted)) => {
+				self.pos += 1;
+				match text.find(':') {
+					Some(idx) if !quoted => {
+						let key = text[..idx].to_lowercase();
+						if !FILTER_KEYS.contains(&key.as_str()) {
+							return Err(syntax_error(&format!("Unknown filter '{}'", key), start));
+						}
+						let value = text[idx + 1..].to_string();
+						if value.len() == 0 {
+							return Err(syntax_error(&format!("Missing value for filter '{}'", key), start));
+						}
+						Ok(Ast::Filter(key, value, start))
+					},
+					_ => Ok(Ast::Term(text, start))
+				}
+			}
+		}
+	}
+}
+
+/// Flatten nested AND/OR of the same kind
+fn normalize(ast: Ast) -> Ast {
+	match ast {
+		Ast::And(items, p) => Ast::And(items.into_iter().map(normalize).flat_map(|i| match i { Ast::And(sub, _) => sub, o => vec![o] }).collect(), p),
+		Ast::Or(items, p) => Ast::Or(items.into_iter().map(normalize).flat_map(|i| match i { Ast::Or(sub, _) => sub, o => vec![o] }).collect(), p),
+		Ast::Not(inner, p) => match normalize(*inner) {
+			Ast::Not(inner2, _) => *inner2,
+			o => Ast::Not(Box::new(o), p)
+		},
+		o => o
+	}
+}
+
+fn parse_query(query: &str) -> Result<Ast, QuerySyntaxError> {
+	let mut parser = Parser {
+		tokens: tokenize(query)?,
+		pos: 0,
+		end: query.chars().count()
+	};
+	let ast = parser.parse_or()?;
+	if parser.peek().is_some() {
+		return Err(syntax_error("Unexpected ')'", parser.offset()));
+	}
+	Ok(normalize(ast))
+}
+
+fn collect_terms(ast: &Ast, out: &mut Vec<(String, usize)>) {
+	match ast {
+		Ast::And(items, _) | Ast::Or(items, _) => items.iter().for_each(|i| collect_terms(i, out)),
+		Ast::Not(inner, _) => collect_terms(inner, out),
+		Ast::Term(t, p) => out.push((t.clone(), *p)),
+		Ast::Filter(_, _, _) => {}
+	}
+}
+
+fn tag_fields(tag: &TagObjectValue) -> (i32, &Vec<MultilingualMapping>, &Vec<String>) {
+	match tag {
+		TagObjectValue::RegularTagObject(t) => (t.tagid, &t.languages, &t.alias),
+		TagObjectValue::AuthorTagObject(t) => (t.tagid, &t.languages, &t.alias)
+	}
+}
+
+fn tag_matches(languages: &Vec<MultilingualMapping>, alias: &Vec<String>, term: &str) -> bool {
+	languages.iter().any(|l| l.value.to_lowercase() == term.to_lowercase()) || alias.iter().any(|a| a.to_lowercase() == term.to_lowercase())
+}
+
+fn tag_name(tag: &TagObjectValue, lang: &str) -> Option<String> {
+	let (_, languages, _) = tag_fields(tag);
+	pick_language(languages, lang, &default_lang_fallback()).map(|l| l.value.clone())
+}
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryNodeKindEnum {
+	And,
+	Or,
+	Not,
+	Tag,
+	Filter
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="Node of a parsed tag query", Context = Context)]
+pub struct QueryNode {
+	pub kind: QueryNodeKindEnum,
+	/// Sub-queries of AND, OR and NOT
+	pub children: Vec<QueryNode>,
+	/// Term or filter value as written in query
+	pub text: Option<String>,
+	/// One of 'site', 'date', 'clearence' for filters
+	pub filter_key: Option<String>,
+	/// Char position in query (start from 0)
+	pub position: i32,
+	/// Resolved tag, null if term is unknown
+	pub tag: Option<TagObjectValue>
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="Term in a tag query that matches no tag", Context = Context)]
+pub struct UnknownQueryTerm {
+	pub term: String,
+	/// Char position in query (start from 0)
+	pub position: i32,
+	/// Similar tags, most used first
+	pub suggestions: Vec<TagObjectValue>
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="explainVideoQuery result", Context = Context)]
+pub struct ExplainVideoQueryResult {
+	/// Parsed query, null if there are syntax errors
+	pub tree: Option<QueryNode>,
+	/// Query rewritten with tag names in requested language, null if there are syntax errors
+	pub normalized_query: Option<String>,
+	pub unknown_terms: Vec<UnknownQueryTerm>,
+	pub errors: Vec<QuerySyntaxError>
+}
+
+fn build_node(ast: &Ast, resolved: &HashMap<String, TagObjectValue>) -> QueryNode {
+	let node = |kind, children, text: Option<String>, filter_key, position: usize, tag| QueryNode {
+		kind: kind,
+		children: children,
+		text: text,
+		filter_key: filter_key,
+		position: position as i32,
+		tag: tag
+	};
+	match ast {
+		Ast::And(items, p) => node(QueryNodeKindEnum::And, items.iter().map(|i| build_node(i, resolved)).collect(), None, None, *p, None),
+		Ast::Or(items, p) => node(QueryNodeKindEnum::Or, items.iter().map(|i| build_node(i, resolved)).collect(), None, None, *p, None),
+		Ast::Not(inner, p) => node(QueryNodeKindEnum::Not, vec![build_node(inner, resolved)], None, None, *p, None),
+		Ast::Term(t, p) => node(QueryNodeKindEnum::Tag, vec![], Some(t.clone()), None, *p, resolved.get(t).cloned()),
+		Ast::Filter(k, v, p) => node(QueryNodeKindEnum::Filter, vec![], Some(v.clone()), Some(k.clone()), *p, None)
+	}
+}
+
+fn render(ast: &Ast, resolved: &HashMap<String, TagObjectValue>, lang: &str, top: bool) -> String {
+	let wrap = |s: String| if top { s } else { format!("({})", s) };
+	match ast {
+		Ast::And(items, _) => wrap(items.iter().map(|i| render(i, resolved, lang, false)).collect::<Vec<_>>().join(" AND ")),
+		Ast::Or(items, _) => wrap(items.iter().map(|i| render(i, resolved, lang, false)).collect::<Vec<_>>().join(" OR ")),
+		Ast::Not(inner, _) => format!("NOT {}", render(inner, resolved, lang, false)),
+		Ast::Term(t, _) => escape_term(&resolved.get(t).and_then(|tag| tag_name(tag, lang)).unwrap_or(t.clone())),
+		Ast::Filter(k, v, _) => format!("{}:{}", k, v)
+	}
+}
+
+/// Look up a term, returns (exact match, suggestions)
+async fn resolveTerm(context: &Context, term: String) -> FieldResult<(Option<i32>, Vec<i32>)> {
+	let found = editTags::listTags_impl(context, editTags::ListTagParameters {
+		query: Some(format!("*{}*", term)),
+		query_regex: Some(false),
+		category: None,
+		order: Some("count".to_string()),
+		offset: Some(0),
+		limit: Some(MAX_SUGGESTIONS * 2)
+	}).await?;
+	let exact = found.tags.iter().find(|t| tag_matches(&t.languages, &t.alias, &term)).map(|t| t.tagid);
+	let suggestions = found.tags.iter().map(|t| t.tagid).take(MAX_SUGGESTIONS as usize).collect::<Vec<_>>();
+	Ok((exact, suggestions))
+}
+
+pub async fn explainVideoQuery_impl(context: &Context, query: String, lang: Option<String>) -> FieldResult<ExplainVideoQueryResult> {
+	let lang = context.lang_or_default(lang);
+	let ast = match parse_query(&query) {
+		Ok(ast) => ast,
+		Err(e) => return Ok(ExplainVideoQueryResult {
+			tree: None,
+			normalized_query: None,
+			unknown_terms: vec![],
+			errors: vec![e]
+		})
+	};
+	let mut terms = vec![];
+	collect_terms(&ast, &mut terms);
+	let mut seen = std::collections::HashSet::new();
+	terms.retain(|(t, _)| seen.insert(t.clone()));
+	terms.truncate(MAX_RESOLVED_TERMS);
+	let lookups = join_all(terms.iter().map(|(t, _)| resolveTerm(context, t.clone()))).await.into_iter().collect::<FieldResult<Vec<_>>>()?;
+	let mut tagids = lookups.iter().flat_map(|(exact, sugg)| exact.iter().chain(sugg.iter()).cloned()).collect::<Vec<_>>();
+	tagids.sort();
+	tagids.dedup();
+	let tagobjs = if tagids.len() > 0 {
+		editTags::getTagObjectsBatch_impl(context, editTags::GetTagObjectsBatchParameters { tagid: tagids }).await?
+	} else {
+		vec![]
+	};
+	let by_id = tagobjs.into_iter().map(|t| (tag_fields(&t).0, t)).collect::<HashMap<_, _>>();
+	let mut resolved = HashMap::new();
+	let mut unknown_terms = vec![];
+	for ((term, position), (exact, sugg)) in terms.into_iter().zip(lookups.into_iter()) {
+		match exact.and_then(|id| by_id.get(&id)) {
+			Some(tag) => { resolved.insert(term, tag.clone()); },
+			None => unknown_terms.push(UnknownQueryTerm {
+				term: term,
+				position: position as i32,
+				suggestions: sugg.iter().filter_map(|id| by_id.get(id).cloned()).collect()
+			})
+		}
+	}
+	Ok(ExplainVideoQueryResult {
+		tree: Some(build_node(&ast, &resolved)),
+		normalized_query: Some(render(&ast, &resolved, &lang, true)),
+		unknown_terms: unknown_terms,
+		errors: vec![]
+	})
+}
+
+#[test]
+fn parse_tag_query() {
+	let ast = parse_query("a (b OR c OR (d OR e)) AND NOT NOT site:bilibili").unwrap();
+	assert_eq!(render(&ast, &HashMap::new(), "ENG", true), "a AND (b OR c OR d OR e) AND site:bilibili");
+	assert_eq!(parse_query("\"x y\" ANY(p q)").map(|a| render(&a, &HashMap::new(), "ENG", true)), Ok("\"x y\" AND (p OR q)".to_string()));
+	assert_eq!(parse_query("a (b").unwrap_err().position, 4);
+	assert_eq!(parse_query("a \"b").unwrap_err().position, 2);
+	assert_eq!(parse_query("a OR").unwrap_err().position, 4);
+	assert_eq!(parse_query("foo:bar").unwrap_err().message, "Unknown filter 'foo'");
+	assert_eq!(parse_query("a or not \"or\"").map(|a| render(&a, &HashMap::new(), "ENG", true)), Ok("a OR NOT \"or\"".to_string()));
+}
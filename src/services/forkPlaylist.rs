@@ -0,0 +1,267 @@
+
+use juniper::graphql_value;
+
+
+use juniper::FieldResult;
+use rand::Rng;
+use serde_json::json;
+
+use crate::common::*;
+
+use chrono::{DateTime, Duration, Utc};
+use serde_derive::{Serialize, Deserialize};
+use bson::oid::ObjectId;
+use crate::models::{Error, RestResult, Playlist, Video, valid_tag_ids};
+use crate::context::Context;
+use crate::guard::Guard;
+use crate::services::{playlist, users};
+
+/// Lists up to this many videos are forked before the mutation returns
+const SYNC_FORK_LIMIT: i32 = 100;
+/// Num of videos fetched per page when copying
+const FORK_PAGE_SIZE: i32 = 100;
+/// Finished jobs are forgotten after this many minutes
+const FORK_JOB_TTL_MINUTES: i64 = 60;
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ForkStatusEnum {
+	Running,
+	Finished,
+	Failed
+}
+
+/// Jobs only live in memory of the gateway process running them, a fork interrupted by
+/// a restart is continued with `resumeFork` on the new playlist
+#[derive(Clone, Debug)]
+pub struct ForkJob {
+	pub job_id: String,
+	/// User who started this job, only they can see its progress
+	pub owner: ObjectId,
+	pub src_pid: String,
+	pub dst_pid: String,
+	pub status: ForkStatusEnum,
+	pub total: i32,
+	pub copied: i32,
+	pub error: Option<String>,
+	pub updated_at: DateTime<Utc>
+}
+
+#[juniper::graphql_object(Context = Context)]
+#[graphql(description="Progress of forking a playlist")]
+impl ForkJob {
+	pub fn id(&self) -> &String {
+		&self.job_id
+	}
+	pub fn status(&self) -> ForkStatusEnum {
+		self.status
+	}
+	/// Num of videos in source playlist
+	pub fn total(&self) -> i32 {
+		self.total
+	}
+	/// Num of videos copied so far
+	pub fn copied(&self) -> i32 {
+		self.copied
+	}
+	/// Error code if failed
+	pub fn error(&self) -> &Option<String> {
+		&self.error
+	}
+	/// The source playlist
+	pub async fn source(&self, context: &Context) -> FieldResult<Playlist> {
+		playlist::getPlaylist_impl(context, playlist::GetPlaylistParameters {
+			pid: self.src_pid.clone()
+		}).await
+	}
+	/// The new playlist
+	pub async fn playlist(&self, context: &Context) -> FieldResult<Playlist> {
+		playlist::getPlaylist_impl(context, playlist::GetPlaylistParameters {
+			pid: self.dst_pid.clone()
+		}).await
+	}
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="forkPlaylist required parameters", Context = Context)]
+pub struct ForkPlaylistParameters {
+	/// Source playlist ID
+	pub pid: String,
+	/// Title of new playlist, default to source title
+	pub title: Option<String>,
+	/// If only owner can see new playlist, default false
+	pub private: Option<bool>,
+	/// If only owner can edit new playlist, default to the setting of source playlist
+	pub private_edit: Option<bool>
+}
+
+fn update_job(context: &Context, job: &ForkJob) {
+	let mut jobs = context.shared.fork_jobs.lock().unwrap();
+	let now = Utc::now();
+	jobs.retain(|_, j| j.status == ForkStatusEnum::Running || now - j.updated_at < Duration::minutes(FORK_JOB_TTL_MINUTES));
+	let mut job = job.clone();
+	job.updated_at = now;
+	jobs.insert(job.job_id.clone(), job);
+}
+
+/// Copy every video of source playlist in order, progress is recorded in shared state
+async fn copyVideos(context: &Context, mut job: ForkJob) -> ForkJob {
+	let mut offset = job.copied;
+	loop {
+		let page: Vec<Video> = match playlist::getPlaylistContent_impl(context, playlist::GetPlaylistContentParameters {
+			pid: job.src_pid.clone(),
+			offset: Some(offset),
+			limit: Some(FORK_PAGE_SIZE)
+		}).await {
+			Ok(p) => p,
+			Err(e) => {
+				job.status = ForkStatusEnum::Failed;
+				job.error = Some(e.message().to_string());
+				update_job(context, &job);
+				return job;
+			}
+		};
+		let page_len = page.len() as i32;
+		for v in page {
+			if let Err(e) = playlist::addVideo_impl(context, &playlist::AddVideoToPlaylistParameters {
+				pid: job.dst_pid.clone(),
+				vid: v._id.to_string(),
+				rank: None
+			}).await {
+				job.status = ForkStatusEnum::Failed;
+				job.error = Some(e.message().to_string());
+				update_job(context, &job);
+				return job;
+			}
+			job.copied += 1;
+		}
+		update_job(context, &job);
+		offset += page_len;
+		if page_len < FORK_PAGE_SIZE {
+			break;
+		}
+	}
+	job.status = ForkStatusEnum::Finished;
+	update_job(context, &job);
+	job
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CreateForkResult {
+	pub pid: String
+}
+
+pub async fn forkPlaylist_impl(context: &Context, para: ForkPlaylistParameters) -> FieldResult<ForkJob> {
+	let viewer = Guard::LoggedIn.check(context).await?;
+	let src = playlist::getPlaylist_impl(context, playlist::GetPlaylistParameters {
+		pid: para.pid.clone()
+	}).await?;
+	let req = json!({
+		"title": para.title.unwrap_or(src.item.title.clone()),
+		"desc": src.item.desc,
+		"cover": src.item.cover,
+		"private": para.private.unwrap_or(false),
+		"privateEdit": para.private_edit.unwrap_or(src.item.privateEdit),
+		"forked_from": para.pid
+	});
+	let result = postJSON!(CreateForkResult, format!("{}/lists/new.do", BACKEND_URL), req, context);
+	if result.status != "SUCCEED" {
+		return Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		);
+	}
+	let dst_pid = result.data.unwrap().pid;
+	let tag_ids = valid_tag_ids(&src.tags);
+	if tag_ids.len() > 0 {
+		playlist::editPlaylistTagIds_impl(context, playlist::EditPlaylistTagIdsParameters {
+			playlist_id: dst_pid.clone(),
+			tags: tag_ids,
+			edit_behaviour: "replace".to_string(),
+			not_found_behaviour: None,
+			user_language: None
+		}).await?;
+	}
+	let job = ForkJob {
+		job_id: new_job_id(),
+		owner: viewer.user._id,
+		src_pid: para.pid,
+		dst_pid: dst_pid,
+		status: ForkStatusEnum::Running,
+		total: src.item.videos,
+		copied: 0,
+		error: None,
+		updated_at: Utc::now()
+	};
+	start_job(context, job).await
+}
+
+fn new_job_id() -> String {
+	format!("{:016x}", rand::thread_rng().gen::<u64>())
+}
+
+/// Small forks finish before returning, larger ones continue in background with the caller's session
+async fn start_job(context: &Context, job: ForkJob) -> FieldResult<ForkJob> {
+	update_job(context, &job);
+	if job.total - job.copied <= SYNC_FORK_LIMIT {
+		Ok(copyVideos(context, job).await)
+	} else {
+		let ctx = context.clone();
+		let ret = job.clone();
+		actix_web::rt::spawn(async move {
+			copyVideos(&ctx, job).await;
+		});
+		Ok(ret)
+	}
+}
+
+/// Continue copying into fork `pid` after its last video, for forks whose job was lost or failed
+pub async fn resumeFork_impl(context: &Context, pid: String) -> FieldResult<ForkJob> {
+	let viewer = Guard::LoggedIn.check(context).await?;
+	if let Some(running) = context.shared.fork_jobs.lock().unwrap().values().find(|j| j.dst_pid == pid && j.status == ForkStatusEnum::Running) {
+		if running.owner == viewer.user._id {
+			return Ok(running.clone());
+		}
+	}
+	let dst = playlist::getPlaylist_impl(context, playlist::GetPlaylistParameters {
+		pid: pid.clone()
+	}).await?;
+	let src_pid = match dst.item.forked_from.as_ref() {
+		Some(p) => p.to_string(),
+		None => return Err(
+			juniper::FieldError::new(
+				"INCORRECT_REQUEST",
+				graphql_value!({
+					"Playlist is not a fork"
+				}),
+			)
+		)
+	};
+	let src = playlist::getPlaylist_impl(context, playlist::GetPlaylistParameters {
+		pid: src_pid.clone()
+	}).await?;
+	let job = ForkJob {
+		job_id: new_job_id(),
+		owner: viewer.user._id,
+		src_pid: src_pid,
+		dst_pid: pid,
+		status: ForkStatusEnum::Running,
+		total: src.item.videos,
+		copied: dst.item.videos.min(src.item.videos),
+		error: None,
+		updated_at: Utc::now()
+	};
+	start_job(context, job).await
+}
+
+/// None if job is unknown, expired, lost by a restart or started by another user
+pub async fn getForkProgress_impl(context: &Context, job_id: String) -> FieldResult<Option<ForkJob>> {
+	let viewer = match users::viewer_impl(context).await? {
+		Some(v) => v,
+		None => return Ok(None)
+	};
+	Ok(context.shared.fork_jobs.lock().unwrap().get(&job_id).filter(|j| j.owner == viewer.user._id).cloned())
+}
@@ -26,11 +26,12 @@ pub struct GetVideoResponse {
 pub struct GetVideoParameters {
 	/// Video ID
 	pub vid: String,
-	/// Language
-	pub lang: String
+	/// Language, default to request language
+	pub lang: Option<String>
 }
 
-pub async fn getVideo_impl(context: &Context, para: GetVideoParameters) -> FieldResult<Video> {
+pub async fn getVideo_impl(context: &Context, mut para: GetVideoParameters) -> FieldResult<Video> {
+	para.lang = Some(context.lang_or_default(para.lang.take()));
 	let result = postJSON!(GetVideoResponse, format!("{}/getvideo.do", BACKEND_URL), para, context);
 	if result.status == "SUCCEED" {
 		let resp = result.data.unwrap();
@@ -87,6 +88,47 @@ pub async fn getRelatedVideo_impl(context: &Context, para: GetRelatedVideoParame
 	}
 }
 
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="A single part of a multi-part video", Context = Context)]
+pub struct VideoPart {
+	/// Page number (start from 1)
+	pub page: i32,
+	/// bilibili cid of this part
+	pub cid: Option<String>,
+	pub part_name: Option<String>,
+	pub video: Video
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ListVideoPartsResponse {
+	pub videos: Vec<Video>
+}
+
+/// All parts of the work this video belongs to, ordered by page
+pub async fn listVideoParts_impl(context: &Context, vid: String) -> FieldResult<Vec<VideoPart>> {
+	let req = serde_json::json!({
+		"vid": vid
+	});
+	let result = postJSON!(ListVideoPartsResponse, format!("{}/videos/list_parts.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		Ok(result.data.unwrap().videos.into_iter().enumerate().map(|(i, v)| VideoPart {
+			page: i as i32 + 1,
+			cid: v.item.cid.map(|cid| format!("{}", cid)),
+			part_name: v.item.part_name.clone(),
+			video: v
+		}).collect::<Vec<_>>())
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
 #[test]
 fn untyped_example() -> Result<(), Box<dyn std::error::Error>> {
 	use serde_json;
@@ -14,6 +14,7 @@ use crate::models::*;
 use std::convert::{TryFrom, TryInto};
 use crate::models::{Meta, Error, RestResult, Video, VideoItem};
 use crate::context::Context;
+use crate::services::videoQuery::{self, VideoQueryInput};
 
 #[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
 #[graphql(description="listVideo required parameters", Context = Context)]
@@ -32,10 +33,13 @@ pub struct ListVideoParameters {
 	pub additional_constraint: Option<String>,
 	/// If true, no placeholder items will be shown
 	pub hide_placeholder: Option<bool>,
-	/// User language
+	/// User language, default to request language
 	pub lang: Option<String>,
 	/// Add tags_readable field to every result item
-	pub human_readable_tag: Option<bool>
+	pub human_readable_tag: Option<bool>,
+	/// Structured query, compiled into `query`, can not be used together with `query`
+	#[serde(skip)]
+	pub query_tree: Option<VideoQueryInput>
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -92,7 +96,12 @@ impl ListVideoResult {
 }
 
 
-pub async fn listVideo_impl(context: &Context, para: ListVideoParameters) -> FieldResult<ListVideoResult> {
+pub async fn listVideo_impl(context: &Context, mut para: ListVideoParameters) -> FieldResult<ListVideoResult> {
+	para.lang = Some(context.lang_or_default(para.lang.take()));
+	if para.query_tree.is_some() {
+		para.query = videoQuery::merge_query(para.query.take(), para.query_tree.as_ref())?;
+		para.qtype = Some("tag".to_string());
+	}
 	let result = if para.query.is_none() {
 		postJSON!(ListVideoResult, format!("{}/listvideo.do", BACKEND_URL), para, context)
 	} else {
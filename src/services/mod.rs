@@ -1,10 +1,16 @@
 
 pub mod listVideo;
+pub mod videoQuery;
+pub mod explainQuery;
+pub mod search;
 pub mod getVideo;
 pub mod editTags;
 pub mod authorDB;
 pub mod pvsubscription;
 pub mod playlist;
+pub mod playlistFile;
+pub mod forkPlaylist;
+pub mod playlistCollaborators;
 pub mod users;
 pub mod rating;
 pub mod tags;
@@ -13,6 +19,11 @@ pub mod notification;
 pub mod comment;
 pub mod leaderboard;
 pub mod tagHistory;
+pub mod tagImplication;
+pub mod relatedTags;
+pub mod apiToken;
+pub mod upload;
 
 pub mod postvideo;
 pub mod editVideo;
+pub mod videoUrl;
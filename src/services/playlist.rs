@@ -3,8 +3,10 @@ use juniper::graphql_value;
 
 
 use juniper::FieldResult;
+use serde_json::json;
 
-use crate::models::VideoRank;
+use crate::models::{TagObjectValue, VideoRank};
+use crate::services::{editTags, editVideo::EditVideoTagsRespObject};
 use crate::{common::*, models::{Playlist, TagCategoryEnum, TagCategoryItem}};
 
 use chrono::{DateTime, Utc};
@@ -25,6 +27,23 @@ pub struct ResultantPlaylist {
 	pub comment_thread: Option<ObjectId>
 }
 
+impl ResultantPlaylist {
+	pub fn to_playlist(&self) -> Playlist {
+		Playlist {
+			_id: self._id.clone(),
+			item: self.item.clone(),
+			meta: self.meta.clone(),
+			clearence: self.clearence,
+			editable: None,
+			owner: None,
+			tags: self.tags.clone(),
+			tag_by_category: None,
+			comment_thread: self.comment_thread.clone(),
+			tag_objects: Default::default()
+		}
+	}
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct GetPlaylistMetadataResult {
 	pub editable: bool,
@@ -83,7 +102,8 @@ pub async fn getPlaylist_impl(context: &Context, para: GetPlaylistParameters) ->
 			owner: Some(r.owner),
 			tags: r.playlist.tags,
 			tag_by_category: Some(catemap),
-			comment_thread: r.playlist.comment_thread
+			comment_thread: r.playlist.comment_thread,
+			tag_objects: Default::default()
 		})
 	} else {
 		Err(
@@ -140,17 +160,7 @@ pub struct ListPlaylistResult {
 #[juniper::graphql(description="List playlist result")]
 impl ListPlaylistResult {
 	pub fn playlists(&self) -> Vec<Playlist> {
-		self.playlists.iter().map(|r| Playlist {
-			_id: r._id.clone(),
-			item: r.item.clone(),
-			meta: r.meta.clone(),
-			clearence: r.clearence,
-			editable: None,
-			owner: None,
-			tags: r.tags.clone(),
-			tag_by_category: None,
-			comment_thread: r.comment_thread.clone()
-		}).collect::<Vec<_>>()
+		self.playlists.iter().map(|r| r.to_playlist()).collect::<Vec<_>>()
 	}
 	pub fn count(&self) -> &i32 {
 		&self.count
@@ -180,6 +190,105 @@ pub async fn listPlaylist_impl(context: &Context, para: ListPlaylistParameters)
 	}
 }
 
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="listMyPlaylists required parameters", Context = Context)]
+pub struct ListMyPlaylistsParameters {
+	/// Offset (start from 0)
+	pub offset: Option<i32>,
+	/// Num of item in a page
+	pub limit: Option<i32>,
+	/// List order, one of 'latest', 'oldest', 'last_modified'
+	pub order: Option<String>
+}
+
+/// List playlists created by current user, private ones included
+pub async fn listMyPlaylists_impl(context: &Context, para: ListMyPlaylistsParameters) -> FieldResult<ListPlaylistResult> {
+	let result = postJSON!(ListPlaylistResult, format!("{}/lists/myplaylists.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		Ok(result.data.unwrap())
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="listUserPlaylists required parameters", Context = Context)]
+pub struct ListUserPlaylistsParameters {
+	/// User ID
+	pub uid: String,
+	/// Offset (start from 0)
+	pub offset: Option<i32>,
+	/// Num of item in a page
+	pub limit: Option<i32>,
+	/// List order, one of 'latest', 'oldest', 'last_modified'
+	pub order: Option<String>
+}
+
+/// List public playlists created by given user
+pub async fn listUserPlaylists_impl(context: &Context, para: ListUserPlaylistsParameters) -> FieldResult<ListPlaylistResult> {
+	let result = postJSON!(ListPlaylistResult, format!("{}/lists/list_by_user.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		Ok(result.data.unwrap())
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GetFavouritesResult {
+	pub pid: String
+}
+
+/// Current user's favourites playlist, created by backend on first access
+pub async fn getFavourites_impl(context: &Context) -> FieldResult<Playlist> {
+	let result = postJSON!(GetFavouritesResult, format!("{}/lists/get_favourites.do", BACKEND_URL), EmptyJSON::new(), context);
+	if result.status == "SUCCEED" {
+		getPlaylist_impl(context, GetPlaylistParameters {
+			pid: result.data.unwrap().pid
+		}).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn addToFavourites_impl(context: &Context, vid: String) -> FieldResult<Playlist> {
+	let favourites = getFavourites_impl(context).await?;
+	addVideoToPlaylist_impl(context, AddVideoToPlaylistParameters {
+		pid: favourites._id.to_string(),
+		vid: vid,
+		rank: None
+	}).await
+}
+
+pub async fn removeFromFavourites_impl(context: &Context, vid: String) -> FieldResult<Playlist> {
+	let favourites = getFavourites_impl(context).await?;
+	removeVideoFromPlaylist_impl(context, RemoveVideoFromPlaylistParameters {
+		pid: favourites._id.to_string(),
+		vid: vid
+	}).await
+}
+
 #[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
 #[graphql(description="listAdjacentVideos required parameters", Context = Context)]
 pub struct ListAdjacentVideosParameters {
@@ -212,3 +321,264 @@ pub async fn listAdjacentVideos_impl(context: &Context, para: ListAdjacentVideos
 		)
 	}
 }
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="createPlaylist required parameters", Context = Context)]
+pub struct CreatePlaylistParameters {
+	/// Title
+	pub title: String,
+	/// Description
+	pub desc: String,
+	/// Cover image file key
+	pub cover: Option<String>,
+	/// If only owner can see this playlist, default false
+	pub private: Option<bool>,
+	/// If only owner can edit this playlist, default true
+	pub privateEdit: Option<bool>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CreatePlaylistResult {
+	pub pid: String
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="updatePlaylist required parameters, fields not set are left unchanged", Context = Context)]
+pub struct UpdatePlaylistParameters {
+	/// Playlist ID
+	pub pid: String,
+	/// Title
+	pub title: Option<String>,
+	/// Description
+	pub desc: Option<String>,
+	/// Cover image file key
+	pub cover: Option<String>,
+	/// If only owner can see this playlist
+	pub private: Option<bool>,
+	/// If only owner can edit this playlist
+	pub privateEdit: Option<bool>
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="addVideoToPlaylist required parameters", Context = Context)]
+pub struct AddVideoToPlaylistParameters {
+	/// Playlist ID
+	pub pid: String,
+	/// Video ID
+	pub vid: String,
+	/// Rank of video in playlist, default to last position
+	pub rank: Option<i32>
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="removeVideoFromPlaylist required parameters", Context = Context)]
+pub struct RemoveVideoFromPlaylistParameters {
+	/// Playlist ID
+	pub pid: String,
+	/// Video ID
+	pub vid: String
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="movePlaylistVideo required parameters", Context = Context)]
+pub struct MovePlaylistVideoParameters {
+	/// Playlist ID
+	pub pid: String,
+	/// Video ID
+	pub vid: String,
+	/// New rank of video (start from 0)
+	pub rank: i32
+}
+
+pub async fn createPlaylist_impl(context: &Context, para: CreatePlaylistParameters) -> FieldResult<Playlist> {
+	let result = postJSON!(CreatePlaylistResult, format!("{}/lists/new.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		getPlaylist_impl(context, GetPlaylistParameters {
+			pid: result.data.unwrap().pid
+		}).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn updatePlaylist_impl(context: &Context, para: UpdatePlaylistParameters) -> FieldResult<Playlist> {
+	let result = postJSON!(EmptyJSON, format!("{}/lists/update_metadata.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		getPlaylist_impl(context, GetPlaylistParameters {
+			pid: para.pid
+		}).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn deletePlaylist_impl(context: &Context, pid: String) -> FieldResult<bool> {
+	let req = json!({
+		"pid": pid
+	});
+	let result = postJSON!(EmptyJSON, format!("{}/lists/del_playlist.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		Ok(true)
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+/// Add a video without reloading the playlist
+pub async fn addVideo_impl(context: &Context, para: &AddVideoToPlaylistParameters) -> FieldResult<()> {
+	let result = postJSON!(EmptyJSON, format!("{}/lists/add_video.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		Ok(())
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn addVideoToPlaylist_impl(context: &Context, para: AddVideoToPlaylistParameters) -> FieldResult<Playlist> {
+	addVideo_impl(context, &para).await?;
+	getPlaylist_impl(context, GetPlaylistParameters {
+		pid: para.pid
+	}).await
+}
+
+pub async fn removeVideoFromPlaylist_impl(context: &Context, para: RemoveVideoFromPlaylistParameters) -> FieldResult<Playlist> {
+	let result = postJSON!(EmptyJSON, format!("{}/lists/del_video.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		getPlaylist_impl(context, GetPlaylistParameters {
+			pid: para.pid
+		}).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn movePlaylistVideo_impl(context: &Context, para: MovePlaylistVideoParameters) -> FieldResult<Playlist> {
+	if para.rank < 0 {
+		return Err(
+			juniper::FieldError::new(
+				"INCORRECT_REQUEST",
+				graphql_value!({
+					"rank must not be negative"
+				}),
+			)
+		);
+	}
+	let result = postJSON!(EmptyJSON, format!("{}/lists/move.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		getPlaylist_impl(context, GetPlaylistParameters {
+			pid: para.pid
+		}).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="editPlaylistTags required parameters", Context = Context)]
+pub struct EditPlaylistTagsParameters {
+	/// Playlist ID
+	pub playlist_id: String,
+	/// Tags
+	pub tags: Vec<String>,
+	/// One of 'replace', 'append', 'remove'
+	pub edit_behaviour: String,
+	/// Behaviour if a tag does not exist, one of 'ignore', 'error', 'append', default 'ignore'
+	pub not_found_behaviour: Option<String>,
+	/// User language used for adding tags, default is 'ENG'
+	pub user_language: Option<String>,
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="editPlaylistTagIds required parameters", Context = Context)]
+pub struct EditPlaylistTagIdsParameters {
+	/// Playlist ID
+	pub playlist_id: String,
+	/// Tags
+	pub tags: Vec<i32>,
+	/// One of 'replace', 'append', 'remove'
+	pub edit_behaviour: String,
+	/// Behaviour if a tag does not exist, one of 'ignore', 'error', default 'ignore'
+	pub not_found_behaviour: Option<String>,
+	/// User language used for adding tags, default is 'ENG'
+	pub user_language: Option<String>,
+}
+
+pub async fn editPlaylistTags_impl(context: &Context, para: EditPlaylistTagsParameters) -> FieldResult<Vec<TagObjectValue>> {
+	let result = postJSON!(EditVideoTagsRespObject, format!("{}/lists/edittags.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		let tagids = result.data.unwrap().tagids;
+		editTags::getTagObjectsBatch_impl(context, editTags::GetTagObjectsBatchParameters {
+			tagid: tagids
+		}).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn editPlaylistTagIds_impl(context: &Context, para: EditPlaylistTagIdsParameters) -> FieldResult<Vec<TagObjectValue>> {
+	let result = postJSON!(EditVideoTagsRespObject, format!("{}/lists/edittagids.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		let tagids = result.data.unwrap().tagids;
+		editTags::getTagObjectsBatch_impl(context, editTags::GetTagObjectsBatchParameters {
+			tagid: tagids
+		}).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
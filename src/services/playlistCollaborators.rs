@@ -0,0 +1,176 @@
+
+use juniper::graphql_value;
+
+
+use juniper::FieldResult;
+use serde_json::json;
+
+use crate::common::*;
+
+use serde_derive::{Serialize, Deserialize};
+use bson::oid::ObjectId;
+use futures::future::join_all;
+use crate::models::{Error, RestResult, Playlist, Video};
+use crate::context::Context;
+use crate::services::{getVideo, playlist, users};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ListCollaboratorsResult {
+	pub uids: Vec<ObjectId>
+}
+
+pub async fn listCollaborators_impl(context: &Context, pid: String) -> FieldResult<Vec<users::User>> {
+	let req = json!({
+		"pid": pid
+	});
+	let result = postJSON!(ListCollaboratorsResult, format!("{}/lists/collaborators/list.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		join_all(result.data.unwrap().uids.into_iter().map(|uid| users::getUser_impl(context, users::GetUserParameters {
+			uid: uid.to_string()
+		}))).await.into_iter().collect()
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="add or remove playlist collaborator required parameters", Context = Context)]
+pub struct PlaylistCollaboratorParameters {
+	/// Playlist ID
+	pub pid: String,
+	/// User ID
+	pub uid: String
+}
+
+pub async fn addPlaylistCollaborator_impl(context: &Context, para: PlaylistCollaboratorParameters) -> FieldResult<Playlist> {
+	let result = postJSON!(EmptyJSON, format!("{}/lists/collaborators/add.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		playlist::getPlaylist_impl(context, playlist::GetPlaylistParameters {
+			pid: para.pid
+		}).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn removePlaylistCollaborator_impl(context: &Context, para: PlaylistCollaboratorParameters) -> FieldResult<Playlist> {
+	let result = postJSON!(EmptyJSON, format!("{}/lists/collaborators/remove.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		playlist::getPlaylist_impl(context, playlist::GetPlaylistParameters {
+			pid: para.pid
+		}).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistActivityOpEnum {
+	/// Video added
+	Add,
+	/// Video removed
+	Del,
+	/// Video moved to another rank
+	Move
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlaylistActivity {
+	pub op: PlaylistActivityOpEnum,
+	pub vid: ObjectId,
+	pub rank_from: Option<i32>,
+	pub rank_to: Option<i32>,
+	pub user_id: ObjectId,
+	pub time: bson::DateTime
+}
+
+#[juniper::graphql_object(Context = Context)]
+#[graphql(description="A single change made to a playlist")]
+impl PlaylistActivity {
+	pub fn op(&self) -> PlaylistActivityOpEnum {
+		self.op
+	}
+	/// Rank before this change, null for 'add'
+	pub fn rank_from(&self) -> Option<i32> {
+		self.rank_from
+	}
+	/// Rank after this change, null for 'del'
+	pub fn rank_to(&self) -> Option<i32> {
+		self.rank_to
+	}
+	pub fn time(&self) -> &bson::DateTime {
+		&self.time
+	}
+	pub async fn video(&self, context: &Context, lang: Option<String>) -> FieldResult<Video> {
+		getVideo::getVideo_impl(context, getVideo::GetVideoParameters {
+			vid: self.vid.to_string(),
+			lang: lang
+		}).await
+	}
+	pub async fn user(&self, context: &Context) -> FieldResult<users::User> {
+		users::getUser_impl(context, users::GetUserParameters {
+			uid: self.user_id.to_string()
+		}).await
+	}
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlaylistActivityResult {
+	pub items: Vec<PlaylistActivity>,
+	pub count: i32
+}
+
+#[juniper::graphql_object(Context = Context)]
+#[graphql(description="Playlist activity log")]
+impl PlaylistActivityResult {
+	/// Latest first
+	pub fn items(&self) -> &Vec<PlaylistActivity> {
+		&self.items
+	}
+	pub fn count(&self) -> i32 {
+		self.count
+	}
+}
+
+pub async fn listPlaylistActivity_impl(context: &Context, pid: String, offset: Option<i32>, limit: Option<i32>) -> FieldResult<PlaylistActivityResult> {
+	let req = json!({
+		"pid": pid,
+		"offset": offset.unwrap_or(0),
+		"limit": limit.unwrap_or(20)
+	});
+	let result = postJSON!(PlaylistActivityResult, format!("{}/lists/activity_log.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		Ok(result.data.unwrap())
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
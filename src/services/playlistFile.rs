@@ -0,0 +1,342 @@
+
+use juniper::graphql_value;
+
+
+use juniper::FieldResult;
+use futures::StreamExt;
+
+use crate::common::*;
+
+use serde_derive::{Serialize, Deserialize};
+use crate::models::{Playlist, Video};
+use crate::context::Context;
+use crate::services::{playlist, postvideo};
+
+/// Num of videos fetched per page when exporting
+const EXPORT_PAGE_SIZE: i32 = 100;
+/// Max num of videos in an imported file
+const MAX_IMPORT_ROWS: usize = 2000;
+/// Max num of rows of an imported file posted at the same time
+const IMPORT_CONCURRENCY: usize = 8;
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub enum PlaylistFileFormatEnum {
+	/// Playlist metadata and videos
+	Json,
+	/// One video per row with header `rank,url,title,site,unique_id`
+	Csv,
+	/// M3U-like list of URLs
+	M3u
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExportedVideo {
+	pub rank: i32,
+	pub url: String,
+	pub title: String,
+	pub site: String,
+	pub unique_id: String
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExportedPlaylist {
+	pub version: i32,
+	pub pid: Option<String>,
+	pub title: String,
+	pub desc: String,
+	pub cover: Option<String>,
+	pub private: Option<bool>,
+	pub privateEdit: Option<bool>,
+	pub videos: Vec<ExportedVideo>
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="Exported playlist file")]
+pub struct PlaylistExport {
+	pub filename: String,
+	pub mime_type: String,
+	pub content: String
+}
+
+fn csv_escape(field: &str) -> String {
+	if field.contains(',') || field.contains('"') || field.contains('\n') || field.contains('\r') {
+		format!("\"{}\"", field.replace('"', "\"\""))
+	} else {
+		field.to_string()
+	}
+}
+
+/// Parse CSV text into rows, quoted fields may contain commas, quotes and newlines
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+	let mut rows = vec![];
+	let mut row = vec![];
+	let mut field = String::new();
+	let mut in_quotes = false;
+	let mut chars = text.chars().peekable();
+	while let Some(c) = chars.next() {
+		if in_quotes {
+			if c == '"' {
+				if chars.peek() == Some(&'"') {
+					field.push('"');
+					chars.next();
+				} else {
+					in_quotes = false;
+				}
+			} else {
+				field.push(c);
+			}
+		} else {
+			match c {
+				'"' => in_quotes = true,
+				',' => row.push(std::mem::take(&mut field)),
+				'\r' => {},
+				'\n' => {
+					row.push(std::mem::take(&mut field));
+					rows.push(std::mem::take(&mut row));
+				},
+				_ => field.push(c)
+			}
+		}
+	}
+	if field.len() > 0 || row.len() > 0 {
+		row.push(field);
+		rows.push(row);
+	}
+	rows.into_iter().filter(|r| !(r.len() == 1 && r[0].trim().len() == 0)).collect::<Vec<_>>()
+}
+
+fn render(playlist: &Playlist, videos: &Vec<ExportedVideo>, format: PlaylistFileFormatEnum) -> FieldResult<String> {
+	Ok(match format {
+		PlaylistFileFormatEnum::Json => {
+			serde_json::to_string_pretty(&ExportedPlaylist {
+				version: 1,
+				pid: Some(playlist._id.to_string()),
+				title: playlist.item.title.clone(),
+				desc: playlist.item.desc.clone(),
+				cover: Some(playlist.item.cover.clone()),
+				private: Some(playlist.item.private),
+				privateEdit: Some(playlist.item.privateEdit),
+				videos: videos.clone()
+			})?
+		},
+		PlaylistFileFormatEnum::Csv => {
+			let mut out = "rank,url,title,site,unique_id\n".to_string();
+			for v in videos.iter() {
+				out.push_str(&format!("{},{},{},{},{}\n", v.rank, csv_escape(&v.url), csv_escape(&v.title), csv_escape(&v.site), csv_escape(&v.unique_id)));
+			}
+			out
+		},
+		PlaylistFileFormatEnum::M3u => {
+			let mut out = format!("#EXTM3U\n#PLAYLIST:{}\n", playlist.item.title.replace('\n', " "));
+			for v in videos.iter() {
+				out.push_str(&format!("#EXTINF:-1,{}\n{}\n", v.title.replace('\n', " "), v.url));
+			}
+			out
+		}
+	})
+}
+
+pub async fn exportPlaylist_impl(context: &Context, pid: String, format: PlaylistFileFormatEnum) -> FieldResult<PlaylistExport> {
+	let playlist_obj = playlist::getPlaylist_impl(context, playlist::GetPlaylistParameters {
+		pid: pid.clone()
+	}).await?;
+	let mut videos: Vec<ExportedVideo> = vec![];
+	let mut offset = 0;
+	loop {
+		let page: Vec<Video> = playlist::getPlaylistContent_impl(context, playlist::GetPlaylistContentParameters {
+			pid: pid.clone(),
+			offset: Some(offset),
+			limit: Some(EXPORT_PAGE_SIZE)
+		}).await?;
+		let page_len = page.len() as i32;
+		for v in page {
+			videos.push(ExportedVideo {
+				rank: videos.len() as i32,
+				url: v.item.url,
+				title: v.item.title,
+				site: v.item.site,
+				unique_id: v.item.unique_id
+			});
+		}
+		offset += page_len;
+		if page_len < EXPORT_PAGE_SIZE || offset >= playlist_obj.item.videos {
+			break;
+		}
+	}
+	let (ext, mime_type) = match format {
+		PlaylistFileFormatEnum::Json => ("json", "application/json"),
+		PlaylistFileFormatEnum::Csv => ("csv", "text/csv"),
+		PlaylistFileFormatEnum::M3u => ("m3u", "audio/x-mpegurl")
+	};
+	Ok(PlaylistExport {
+		filename: format!("playlist-{}.{}", pid, ext),
+		mime_type: mime_type.to_string(),
+		content: render(&playlist_obj, &videos, format)?
+	})
+}
+
+/// URL of a single row and the error code reported for that row if it can not be posted
+fn check_url(url: &str) -> (String, Option<&'static str>) {
+	let url = url.trim();
+	let error = if url.is_empty() {
+		Some("MISSING_URL")
+	} else if !url.starts_with("http://") && !url.starts_with("https://") {
+		Some("INVALID_URL")
+	} else {
+		None
+	};
+	(url.to_string(), error)
+}
+
+/// (title, desc, rows) read from file, each row is a URL and its error code if any
+type ParsedFile = (Option<String>, Option<String>, Vec<(String, Option<&'static str>)>);
+
+fn parse_file(content: &str, format: PlaylistFileFormatEnum) -> FieldResult<ParsedFile> {
+	Ok(match format {
+		PlaylistFileFormatEnum::Json => {
+			let parsed: ExportedPlaylist = serde_json::from_str(content)?;
+			let mut videos = parsed.videos;
+			videos.sort_by_key(|v| v.rank);
+			(Some(parsed.title), Some(parsed.desc), videos.iter().map(|v| check_url(&v.url)).collect::<Vec<_>>())
+		},
+		PlaylistFileFormatEnum::Csv => {
+			let rows = parse_csv(content);
+			let mut url_col = 0;
+			let mut data_rows = &rows[..];
+			if let Some(header) = rows.first() {
+				if let Some(idx) = header.iter().position(|h| h.trim().eq_ignore_ascii_case("url")) {
+					url_col = idx;
+					data_rows = &rows[1..];
+				}
+			}
+			// blank lines are not rows
+			(None, None, data_rows.iter()
+				.filter(|r| r.iter().any(|f| !f.trim().is_empty()))
+				.map(|r| check_url(r.get(url_col).map(|u| u.as_str()).unwrap_or("")))
+				.collect::<Vec<_>>())
+		},
+		PlaylistFileFormatEnum::M3u => {
+			let mut title = None;
+			let mut rows = vec![];
+			for line in content.lines() {
+				let line = line.trim();
+				if line.starts_with("#PLAYLIST:") {
+					title = Some(line["#PLAYLIST:".len()..].to_string());
+				} else if line.len() > 0 && !line.starts_with('#') {
+					rows.push(check_url(line));
+				}
+			}
+			(title, None, rows)
+		}
+	})
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="importPlaylist required parameters", Context = Context)]
+pub struct ImportPlaylistParameters {
+	/// File content
+	pub content: String,
+	/// Format of file
+	pub format: PlaylistFileFormatEnum,
+	/// Title of new playlist, default to title in file
+	pub title: Option<String>,
+	/// Description of new playlist, default to description in file
+	pub desc: Option<String>,
+	/// If only owner can see this playlist, default false
+	pub private: Option<bool>,
+	/// Tags added to every posted video
+	pub tags: Option<Vec<String>>
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="Result of posting a single row of imported file")]
+pub struct ImportPlaylistRowResult {
+	/// Row index (start from 0)
+	pub row: i32,
+	pub url: String,
+	/// Post video task ID if succeed
+	pub task_id: Option<String>,
+	/// Error code if failed
+	pub error: Option<String>
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="importPlaylist result", Context = Context)]
+pub struct ImportPlaylistResult {
+	pub playlist: Playlist,
+	pub rows: Vec<ImportPlaylistRowResult>
+}
+
+pub async fn importPlaylist_impl(context: &Context, para: ImportPlaylistParameters) -> FieldResult<ImportPlaylistResult> {
+	let (file_title, file_desc, rows) = parse_file(&para.content, para.format)?;
+	if rows.len() > MAX_IMPORT_ROWS {
+		return Err(
+			juniper::FieldError::new(
+				"INCORRECT_REQUEST",
+				graphql_value!({
+					"Too many videos in file"
+				}),
+			)
+		);
+	}
+	let playlist_obj = playlist::createPlaylist_impl(context, playlist::CreatePlaylistParameters {
+		title: para.title.or(file_title).unwrap_or("Imported playlist".to_string()),
+		desc: para.desc.or(file_desc).unwrap_or_default(),
+		cover: None,
+		private: para.private,
+		privateEdit: None
+	}).await?;
+	let tags = para.tags.unwrap_or_default();
+	let pid = playlist_obj._id.to_string();
+	let mut rank = 0;
+	let posts = rows.into_iter().enumerate().map(|(i, (url, error))| {
+		let row_rank = rank;
+		if error.is_none() {
+			rank += 1;
+		}
+		let (tags, pid) = (tags.clone(), pid.clone());
+		async move {
+			if let Some(error) = error {
+				return ImportPlaylistRowResult { row: i as i32, url, task_id: None, error: Some(error.to_string()) };
+			}
+			let ret = postvideo::postVideo_impl(context, postvideo::PostVideoRequestData {
+				url: url.clone(),
+				tags,
+				copy: None,
+				pid: Some(pid),
+				rank: Some(row_rank),
+				repost_type: None,
+				tag_merge_behaviour: None
+			}).await;
+			match ret {
+				Ok(r) => ImportPlaylistRowResult { row: i as i32, url, task_id: Some(r.task_id), error: None },
+				Err(e) => ImportPlaylistRowResult { row: i as i32, url, task_id: None, error: Some(e.message().to_string()) }
+			}
+		}
+	}).collect::<Vec<_>>();
+	// rows are posted a few at a time instead of one after another, results keep row order
+	let rows = futures::stream::iter(posts).buffered(IMPORT_CONCURRENCY).collect::<Vec<_>>().await;
+	Ok(ImportPlaylistResult {
+		playlist: playlist_obj,
+		rows
+	})
+}
+
+#[test]
+fn csv_roundtrip() {
+	let rows = parse_csv("rank,url,title\n0,https://a.com/1,\"Hello, \"\"World\"\"\"\n1,https://a.com/2,\"multi\nline\"\n");
+	assert_eq!(rows.len(), 3);
+	assert_eq!(rows[1][2], "Hello, \"World\"");
+	assert_eq!(rows[2][2], "multi\nline");
+	assert_eq!(csv_escape("Hello, \"World\""), "\"Hello, \"\"World\"\"\"");
+}
+
+#[test]
+fn import_rows_report_errors() {
+	let (_, _, rows) = parse_file("rank,url,title\n0,https://a.com/1,a\n\n1,,b\n2,not a url,c\n", PlaylistFileFormatEnum::Csv).unwrap();
+	assert_eq!(rows, vec![
+		("https://a.com/1".to_string(), None),
+		("".to_string(), Some("MISSING_URL")),
+		("not a url".to_string(), Some("INVALID_URL"))
+	]);
+}
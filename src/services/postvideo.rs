@@ -95,3 +95,22 @@ pub async fn batchPostVideo_impl(context: &Context, para: BatchPostVideoRequestD
 		)
 	}
 }
+
+/// Post every part of a multi-part URL, results are grouped as a batch
+pub async fn postVideoAllParts_impl(context: &Context, para: PostVideoRequestData) -> FieldResult<BatchPostVideoResult> {
+	let result = postJSON!(BatchPostVideoResult, format!("{}/postvideo_all_parts.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		let r = result.data.unwrap();
+		Ok(r)
+	} else {
+		let r = result.dataerr.unwrap();
+		Err(
+			juniper::FieldError::new(
+				r.reason,
+				r.aux.map_or(Value::Null, |f| graphql_value!({
+					f
+				})),
+			)
+		)
+	}
+}
@@ -11,6 +11,7 @@ use serde_derive::{Serialize, Deserialize};
 use bson::oid::ObjectId;
 use std::convert::{TryFrom, TryInto};
 use crate::models::*;
+use crate::services::videoQuery::{self, VideoQueryInput};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PVSubscription {
@@ -92,10 +93,13 @@ pub struct ListSubscriptionVideosParameters {
 	pub additional_constraint: Option<String>,
 	/// If true, no placeholder items will be shown
 	pub hide_placeholder: Option<bool>,
-	/// User language
+	/// User language, default to request language
 	pub lang: Option<String>,
 	/// Visible subscriptions, list of obejctid
-	pub visible: Option<Vec<String>>
+	pub visible: Option<Vec<String>>,
+	/// Structured constraints, compiled into `additional_constraint`, can not be used together with `additional_constraint`
+	#[serde(skip)]
+	pub additional_constraint_tree: Option<VideoQueryInput>
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -130,7 +134,9 @@ impl ListSubscriptionVideosResult {
 	}
 }
 
-pub async fn listSubscriptionVideos_impl(context: &Context, para: ListSubscriptionVideosParameters) -> FieldResult<ListSubscriptionVideosResult> {
+pub async fn listSubscriptionVideos_impl(context: &Context, mut para: ListSubscriptionVideosParameters) -> FieldResult<ListSubscriptionVideosResult> {
+	para.lang = Some(context.lang_or_default(para.lang.take()));
+	para.additional_constraint = videoQuery::merge_query(para.additional_constraint.take(), para.additional_constraint_tree.as_ref())?;
 	let result = postJSON!(ListSubscriptionVideosResult, format!("{}/subs/list.do", BACKEND_URL), para, context);
 	if result.status == "SUCCEED" {
 		Ok(result.data.unwrap())
@@ -146,7 +152,9 @@ pub async fn listSubscriptionVideos_impl(context: &Context, para: ListSubscripti
 	}
 }
 
-pub async fn listSubscriptionVideosRandomized_impl(context: &Context, para: ListSubscriptionVideosParameters) -> FieldResult<ListSubscriptionVideosResult> {
+pub async fn listSubscriptionVideosRandomized_impl(context: &Context, mut para: ListSubscriptionVideosParameters) -> FieldResult<ListSubscriptionVideosResult> {
+	para.lang = Some(context.lang_or_default(para.lang.take()));
+	para.additional_constraint = videoQuery::merge_query(para.additional_constraint.take(), para.additional_constraint_tree.as_ref())?;
 	let result = postJSON!(ListSubscriptionVideosResult, format!("{}/subs/list_randomized.do", BACKEND_URL), para, context);
 	if result.status == "SUCCEED" {
 		Ok(result.data.unwrap())
@@ -161,3 +169,110 @@ pub async fn listSubscriptionVideosRandomized_impl(context: &Context, para: List
 		)
 	}
 }
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="add or update subscription required parameters", Context = Context)]
+pub struct SubscriptionParameters {
+	/// Query string
+	pub query: Option<String>,
+	/// Structured query, compiled into `query` like `additional_constraint_tree` of subscription video lists, can not be used together with `query`
+	pub query_tree: Option<VideoQueryInput>,
+	/// Query type, one of 'tag', 'text', default to 'tag'
+	pub qtype: Option<String>,
+	/// Name of this subscription
+	pub name: Option<String>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AddSubscriptionResult {
+	pub subid: ObjectId
+}
+
+fn subscription_request(para: SubscriptionParameters) -> FieldResult<serde_json::Value> {
+	let qtype = if para.query_tree.is_some() { "tag".to_string() } else { para.qtype.unwrap_or("tag".to_string()) };
+	let query = match videoQuery::merge_query(para.query, para.query_tree.as_ref())? {
+		Some(q) => q,
+		None => return Err(
+			juniper::FieldError::new(
+				"INCORRECT_REQUEST",
+				graphql_value!({
+					"Either query or query_tree must be given"
+				}),
+			)
+		)
+	};
+	Ok(serde_json::json!({
+		"query": query,
+		"qtype": qtype,
+		"name": para.name.unwrap_or_default()
+	}))
+}
+
+async fn findSubscription(context: &Context, subid: &ObjectId) -> FieldResult<PVSubscription> {
+	match listSubscriptions_impl(context).await?.into_iter().find(|s| &s._id == subid) {
+		Some(s) => Ok(s),
+		None => Err(
+			juniper::FieldError::new(
+				"SUBSCRIPTION_NOT_EXIST",
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn addSubscription_impl(context: &Context, para: SubscriptionParameters) -> FieldResult<PVSubscription> {
+	let req = subscription_request(para)?;
+	let result = postJSON!(AddSubscriptionResult, format!("{}/subs/add.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		findSubscription(context, &result.data.unwrap().subid).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn updateSubscription_impl(context: &Context, subid: String, para: SubscriptionParameters) -> FieldResult<PVSubscription> {
+	let subid = ObjectId::with_string(&subid)?;
+	let mut req = subscription_request(para)?;
+	req["subid"] = serde_json::json!(subid.to_string());
+	let result = postJSON!(EmptyJSON, format!("{}/subs/update.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		findSubscription(context, &subid).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn removeSubscription_impl(context: &Context, subid: String) -> FieldResult<bool> {
+	let req = serde_json::json!({
+		"subid": subid
+	});
+	let result = postJSON!(EmptyJSON, format!("{}/subs/del.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		Ok(true)
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
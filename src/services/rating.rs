@@ -3,6 +3,7 @@ use juniper::graphql_value;
 
 
 use juniper::FieldResult;
+use serde_json::json;
 
 use crate::{common::*};
 
@@ -86,3 +87,136 @@ pub async fn getRating_impl(context: &Context, para: GetRatingParameters) -> Fie
 		Ok(None)
 	}
 }
+
+pub const MIN_STARS: i32 = 1;
+pub const MAX_STARS: i32 = 10;
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="required parameters for rating a video", Context = Context)]
+pub struct RateVideoParameters {
+	/// ID of video
+	pub vid: String,
+	/// Stars, from 1 to 10
+	pub stars: i32
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="required parameters for rating a playlist", Context = Context)]
+pub struct RatePlaylistParameters {
+	/// ID of playlist
+	pub pid: String,
+	/// Stars, from 1 to 10
+	pub stars: i32
+}
+
+fn check_stars(stars: i32) -> FieldResult<()> {
+	if stars < MIN_STARS || stars > MAX_STARS {
+		return Err(
+			juniper::FieldError::new(
+				"INCORRECT_REQUEST",
+				graphql_value!({
+					"stars must be between 1 and 10"
+				}),
+			)
+		);
+	}
+	Ok(())
+}
+
+/// Unlike getRating_impl, backend errors are returned
+async fn fetchRating(context: &Context, para: GetRatingParameters) -> FieldResult<Rating> {
+	let result = if para.pid.is_some() {
+		postJSON!(GetRatingResult, format!("{}/rating/get_playlist_total.do", BACKEND_URL), para, context)
+	} else {
+		postJSON!(GetRatingResult, format!("{}/rating/get_video_total.do", BACKEND_URL), para, context)
+	};
+	if result.status == "SUCCEED" {
+		let r = result.data.unwrap();
+		Ok(Rating {
+			user_rating: r.user_rating,
+			total_rating: r.total_rating,
+			total_user: r.total_user
+		})
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn rateVideo_impl(context: &Context, para: RateVideoParameters) -> FieldResult<Rating> {
+	check_stars(para.stars)?;
+	let result = postJSON!(EmptyJSON, format!("{}/rating/rate_video.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		fetchRating(context, GetRatingParameters { vid: Some(para.vid), pid: None }).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn ratePlaylist_impl(context: &Context, para: RatePlaylistParameters) -> FieldResult<Rating> {
+	check_stars(para.stars)?;
+	let result = postJSON!(EmptyJSON, format!("{}/rating/rate_playlist.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		fetchRating(context, GetRatingParameters { vid: None, pid: Some(para.pid) }).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn removeVideoRating_impl(context: &Context, vid: String) -> FieldResult<Rating> {
+	let req = json!({
+		"vid": vid
+	});
+	let result = postJSON!(EmptyJSON, format!("{}/rating/remove_video.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		fetchRating(context, GetRatingParameters { vid: Some(vid), pid: None }).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn removePlaylistRating_impl(context: &Context, pid: String) -> FieldResult<Rating> {
+	let req = json!({
+		"pid": pid
+	});
+	let result = postJSON!(EmptyJSON, format!("{}/rating/remove_playlist.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		fetchRating(context, GetRatingParameters { vid: None, pid: Some(pid) }).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
@@ -0,0 +1,128 @@
+
+use juniper::FieldResult;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use chrono::{DateTime, Duration, Utc};
+use futures::future::join_all;
+use crate::models::{TagObjectValue, MultilingualMapping};
+use crate::context::Context;
+use crate::services::{editTags, listVideo};
+use crate::services::videoQuery::escape_term;
+
+/// Num of listVideo pages sampled per tag
+const SAMPLE_PAGES: i32 = 3;
+/// Num of videos per sampled page
+const SAMPLE_PAGE_SIZE: i32 = 100;
+/// Max num of co-occurring tags kept in cache per tag
+const MAX_CACHED_TAGS: usize = 200;
+/// Cached co-occurrences are recomputed after this many minutes
+const CACHE_TTL_MINUTES: i64 = 30;
+/// Default num of related tags returned
+const DEFAULT_RELATED_LIMIT: i32 = 20;
+
+/// Co-occurrence counts of a single tag computed from sampled videos
+#[derive(Clone, Debug)]
+pub struct CoOccurrence {
+	/// (tagid, num of sampled videos having both tags), most frequent first
+	pub counts: Vec<(i32, i32)>,
+	/// Num of sampled videos
+	pub sample_size: i32,
+	pub computed_at: DateTime<Utc>
+}
+
+/// Cached co-occurrences of a tag, locked while they are being sampled so concurrent requests share one sample
+pub type CoOccurrenceSlot = Arc<futures::lock::Mutex<Option<CoOccurrence>>>;
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="Tag appearing together with another tag", Context = Context)]
+pub struct RelatedTag {
+	pub tag: TagObjectValue,
+	/// Num of sampled videos having both tags
+	pub count: i32,
+	/// `count` divided by num of sampled videos, between 0 and 1
+	pub score: f64
+}
+
+/// Count tags of sampled videos tagged with `name`, `context` should not carry credentials since results are shared
+async fn sampleCoOccurrence(context: &Context, tagid: i32, name: &str) -> FieldResult<CoOccurrence> {
+	let pages = join_all((0..SAMPLE_PAGES).map(|page| listVideo::listVideo_impl(context, listVideo::ListVideoParameters {
+		offset: Some(page * SAMPLE_PAGE_SIZE),
+		limit: Some(SAMPLE_PAGE_SIZE),
+		query: Some(escape_term(name)),
+		qtype: Some("tag".to_string()),
+		order: None,
+		additional_constraint: None,
+		hide_placeholder: Some(true),
+		lang: None,
+		human_readable_tag: None,
+		query_tree: None
+	}))).await;
+	let mut counts: HashMap<i32, i32> = HashMap::new();
+	let mut sample_size = 0;
+	for page in pages {
+		let page = page?;
+		sample_size += page.videos.len() as i32;
+		for video in page.videos.iter() {
+			for &t in video.tags.iter().filter(|&&n| n < 2_147_483_647i64 && n != tagid as i64) {
+				*counts.entry(t as i32).or_insert(0) += 1;
+			}
+		}
+	}
+	let mut counts = counts.into_iter().collect::<Vec<_>>();
+	counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+	counts.truncate(MAX_CACHED_TAGS);
+	Ok(CoOccurrence {
+		counts: counts,
+		sample_size: sample_size,
+		computed_at: Utc::now()
+	})
+}
+
+fn is_fresh(co: &CoOccurrence, now: DateTime<Utc>) -> bool {
+	now - co.computed_at < Duration::minutes(CACHE_TTL_MINUTES)
+}
+
+/// Co-occurrences of `tagid` among videos visible to anonymous users, sampled at most once per tag at a time
+async fn getCoOccurrence(context: &Context, tagid: i32, name: &str) -> FieldResult<CoOccurrence> {
+	let slot = context.shared.related_tags.lock().unwrap().entry(tagid).or_default().clone();
+	let mut slot = slot.lock().await;
+	let now = Utc::now();
+	if let Some(cached) = slot.as_ref().filter(|c| is_fresh(c, now)) {
+		return Ok(cached.clone());
+	}
+	let computed = sampleCoOccurrence(&context.anonymous(), tagid, name).await?;
+	*slot = Some(computed.clone());
+	// slots being sampled, including this one, are locked and kept
+	context.shared.related_tags.lock().unwrap().retain(|_, s| match s.try_lock() {
+		Some(c) => matches!(c.as_ref(), Some(c) if is_fresh(c, now)),
+		None => true
+	});
+	Ok(computed)
+}
+
+/// Tags most often used together with `tagid`
+pub async fn relatedTags_impl(context: &Context, tagid: i32, languages: &Vec<MultilingualMapping>, limit: Option<i32>) -> FieldResult<Vec<RelatedTag>> {
+	let name = match languages.first() {
+		Some(m) => m.value.clone(),
+		None => return Ok(vec![])
+	};
+	let co = getCoOccurrence(context, tagid, &name).await?;
+	let limit = limit.unwrap_or(DEFAULT_RELATED_LIMIT).max(0).min(MAX_CACHED_TAGS as i32) as usize;
+	let top = co.counts.iter().take(limit).cloned().collect::<Vec<_>>();
+	if top.len() == 0 {
+		return Ok(vec![]);
+	}
+	let tagobjs = editTags::getTagObjectsBatch_impl(context, editTags::GetTagObjectsBatchParameters {
+		tagid: top.iter().map(|(t, _)| *t).collect()
+	}).await?;
+	let by_id = tagobjs.into_iter().map(|t| (match &t {
+		TagObjectValue::RegularTagObject(r) => r.tagid,
+		TagObjectValue::AuthorTagObject(a) => a.tagid
+	}, t)).collect::<HashMap<_, _>>();
+	Ok(top.into_iter().filter_map(|(t, count)| Some(RelatedTag {
+		tag: by_id.get(&t)?.clone(),
+		count: count,
+		score: count as f64 / co.sample_size.max(1) as f64
+	})).collect())
+}
@@ -0,0 +1,264 @@
+
+use juniper::graphql_value;
+
+
+use juniper::FieldResult;
+use serde_json::json;
+
+use crate::common::*;
+
+use serde_derive::{Serialize, Deserialize};
+use futures::future::join_all;
+use crate::models::{Error, RestResult, Video, Playlist, RegularTagObject, TagCategoryEnum};
+use crate::context::Context;
+use crate::services::{authorDB, editTags, listVideo, playlist, users};
+
+/// Default num of results per type
+const DEFAULT_SEARCH_LIMIT: i32 = 10;
+/// Max num of results per type
+const MAX_SEARCH_LIMIT: i32 = 50;
+/// Categories searched for `Tag` results, authors are searched separately
+const REGULAR_TAG_CATEGORIES: [TagCategoryEnum; 6] = [
+	TagCategoryEnum::General,
+	TagCategoryEnum::Character,
+	TagCategoryEnum::Copyright,
+	TagCategoryEnum::Meta,
+	TagCategoryEnum::Language,
+	TagCategoryEnum::Soundtrack
+];
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub enum SearchTypeEnum {
+	Video,
+	Playlist,
+	Tag,
+	Author,
+	User
+}
+
+#[derive(juniper::GraphQLUnion, Clone)]
+#[graphql(Context = Context)]
+pub enum SearchResultItem {
+	Video(Video),
+	Playlist(Playlist),
+	Tag(RegularTagObject),
+	Author(authorDB::Author),
+	User(users::User)
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="A single search result", Context = Context)]
+pub struct SearchHit {
+	pub kind: SearchTypeEnum,
+	/// Relevance to search text, higher is better
+	pub score: f64,
+	pub item: SearchResultItem
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="Num of matches of a single type", Context = Context)]
+pub struct SearchTypeCount {
+	pub kind: SearchTypeEnum,
+	/// Total num of matches, not only those returned
+	pub count: i32
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="Search of a single type that failed, other types are still returned", Context = Context)]
+pub struct SearchTypeError {
+	pub kind: SearchTypeEnum,
+	/// Error code
+	pub error: String
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="search result", Context = Context)]
+pub struct SearchResult {
+	/// Results of all types, most relevant first
+	pub hits: Vec<SearchHit>,
+	pub counts: Vec<SearchTypeCount>,
+	pub errors: Vec<SearchTypeError>
+}
+
+/// Score how well any of `names` matches `text`, `rank` is the position in backend result
+fn relevance<'a, I: IntoIterator<Item = &'a String>>(text: &str, names: I, rank: usize) -> f64 {
+	let text = text.trim().to_lowercase();
+	let best = names.into_iter().map(|n| {
+		let n = n.to_lowercase();
+		if n == text {
+			1.0
+		} else if n.starts_with(&text) {
+			0.8
+		} else if n.contains(&text) {
+			0.6
+		} else {
+			0.4
+		}
+	}).fold(0.0, f64::max);
+	best - (rank as f64) * 0.001
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SearchUsersResult {
+	pub users: Vec<users::User>,
+	pub count: i32
+}
+
+type TypedHits = FieldResult<Option<(Vec<SearchHit>, i32)>>;
+
+async fn searchVideos(context: &Context, text: &str, limit: i32) -> TypedHits {
+	let ret = listVideo::listVideo_impl(context, listVideo::ListVideoParameters {
+		offset: Some(0),
+		limit: Some(limit),
+		query: Some(text.to_string()),
+		qtype: Some("text".to_string()),
+		order: None,
+		additional_constraint: None,
+		hide_placeholder: Some(true),
+		lang: None,
+		human_readable_tag: None,
+		query_tree: None
+	}).await?;
+	Ok(Some((ret.videos.into_iter().enumerate().map(|(i, v)| SearchHit {
+		kind: SearchTypeEnum::Video,
+		score: relevance(text, std::iter::once(&v.item.title), i),
+		item: SearchResultItem::Video(v)
+	}).collect(), ret.count)))
+}
+
+async fn searchPlaylists(context: &Context, text: &str, limit: i32) -> TypedHits {
+	let ret = playlist::listPlaylist_impl(context, playlist::ListPlaylistParameters {
+		offset: Some(0),
+		limit: Some(limit),
+		query: Some(text.to_string()),
+		order: None,
+		additional_constraint: None
+	}).await?;
+	Ok(Some((ret.playlists.iter().enumerate().map(|(i, p)| SearchHit {
+		kind: SearchTypeEnum::Playlist,
+		score: relevance(text, std::iter::once(&p.item.title), i),
+		item: SearchResultItem::Playlist(p.to_playlist())
+	}).collect(), ret.count)))
+}
+
+async fn searchTags(context: &Context, text: &str, limit: i32, category: &TagCategoryEnum) -> FieldResult<(Vec<RegularTagObject>, i32)> {
+	let ret = editTags::listTags_impl(context, editTags::ListTagParameters {
+		query: Some(format!("*{}*", text)),
+		query_regex: Some(false),
+		category: Some(format!("{:?}", category)),
+		order: Some("count".to_string()),
+		offset: Some(0),
+		limit: Some(limit)
+	}).await?;
+	Ok((ret.tags, ret.count))
+}
+
+fn tag_relevance(text: &str, tag: &RegularTagObject, rank: usize) -> f64 {
+	relevance(text, tag.languages.iter().map(|l| &l.value).chain(tag.alias.iter()), rank)
+}
+
+async fn searchRegularTags(context: &Context, text: &str, limit: i32) -> TypedHits {
+	let rets = join_all(REGULAR_TAG_CATEGORIES.iter().map(|c| searchTags(context, text, limit, c))).await;
+	let mut tags = vec![];
+	let mut count = 0;
+	for ret in rets {
+		let (t, c) = ret?;
+		tags.extend(t);
+		count += c;
+	}
+	// same order backend uses within a single category
+	tags.sort_by(|a, b| b.count.cmp(&a.count));
+	tags.truncate(limit as usize);
+	Ok(Some((tags.into_iter().enumerate().map(|(i, t)| SearchHit {
+		kind: SearchTypeEnum::Tag,
+		score: tag_relevance(text, &t, i),
+		item: SearchResultItem::Tag(t)
+	}).collect(), count)))
+}
+
+async fn searchAuthors(context: &Context, text: &str, limit: i32) -> TypedHits {
+	let (tags, count) = searchTags(context, text, limit, &TagCategoryEnum::Author).await?;
+	let authors = join_all(tags.iter().map(|t| authorDB::getAuthor_impl(context, authorDB::GetAuthorParameters {
+		tagid: t.tagid
+	}))).await;
+	Ok(Some((tags.iter().zip(authors).enumerate().filter_map(|(i, (t, author))| Some(SearchHit {
+		kind: SearchTypeEnum::Author,
+		score: tag_relevance(text, t, i),
+		item: SearchResultItem::Author(author.ok()?)
+	})).collect(), count)))
+}
+
+async fn searchUsers(context: &Context, text: &str, limit: i32) -> TypedHits {
+	let req = json!({
+		"query": text,
+		"offset": 0,
+		"limit": limit
+	});
+	let result = postJSON!(SearchUsersResult, format!("{}/user/search.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		let ret = result.data.unwrap();
+		Ok(Some((ret.users.into_iter().enumerate().map(|(i, u)| SearchHit {
+			kind: SearchTypeEnum::User,
+			score: relevance(text, std::iter::once(&u.username), i),
+			item: SearchResultItem::User(u)
+		}).collect(), ret.count)))
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn search_impl(context: &Context, text: String, types: Option<Vec<SearchTypeEnum>>, limit: Option<i32>) -> FieldResult<SearchResult> {
+	if text.trim().len() == 0 {
+		return Err(
+			juniper::FieldError::new(
+				"INCORRECT_REQUEST",
+				graphql_value!({
+					"Search text must not be empty"
+				}),
+			)
+		);
+	}
+	let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT).max(1).min(MAX_SEARCH_LIMIT);
+	let types = types.unwrap_or(vec![SearchTypeEnum::Video, SearchTypeEnum::Playlist, SearchTypeEnum::Tag, SearchTypeEnum::Author, SearchTypeEnum::User]);
+	let text = text.trim();
+	let wanted = |t| types.contains(&t);
+	let (videos, playlists, tags, authors, users) = futures::join!(
+		async { if wanted(SearchTypeEnum::Video) { searchVideos(context, text, limit).await } else { Ok(None) } },
+		async { if wanted(SearchTypeEnum::Playlist) { searchPlaylists(context, text, limit).await } else { Ok(None) } },
+		async { if wanted(SearchTypeEnum::Tag) { searchRegularTags(context, text, limit).await } else { Ok(None) } },
+		async { if wanted(SearchTypeEnum::Author) { searchAuthors(context, text, limit).await } else { Ok(None) } },
+		async { if wanted(SearchTypeEnum::User) { searchUsers(context, text, limit).await } else { Ok(None) } }
+	);
+	let mut hits = vec![];
+	let mut counts = vec![];
+	let mut errors = vec![];
+	for (kind, ret) in vec![(SearchTypeEnum::Video, videos), (SearchTypeEnum::Playlist, playlists), (SearchTypeEnum::Tag, tags), (SearchTypeEnum::Author, authors), (SearchTypeEnum::User, users)] {
+		match ret {
+			Ok(Some((h, count))) => {
+				hits.extend(h);
+				counts.push(SearchTypeCount {
+					kind: kind,
+					count: count
+				});
+			},
+			Ok(None) => {},
+			Err(e) => errors.push(SearchTypeError {
+				kind: kind,
+				error: e.message().to_string()
+			})
+		}
+	}
+	hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+	Ok(SearchResult {
+		hits: hits,
+		counts: counts,
+		errors: errors
+	})
+}
@@ -0,0 +1,120 @@
+
+use juniper::graphql_value;
+
+
+use juniper::FieldResult;
+use serde_json::json;
+
+use crate::common::*;
+
+use serde_derive::{Serialize, Deserialize};
+use crate::models::{Error, RestResult, TagObjectValue};
+use crate::context::Context;
+use crate::services::editTags;
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
+#[graphql(description="add or remove tag implication required parameters", Context = Context)]
+pub struct TagImplicationParameters {
+	/// Tag ID which implies `dst`
+	pub src: i32,
+	/// Tag ID implied by `src`
+	pub dst: i32
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TagImplicationsResult {
+	pub tagids: Vec<i32>
+}
+
+pub async fn addTagImplication_impl(context: &Context, para: TagImplicationParameters) -> FieldResult<bool> {
+	if para.src == para.dst {
+		return Err(
+			juniper::FieldError::new(
+				"INCORRECT_REQUEST",
+				graphql_value!({
+					"A tag can not imply itself"
+				}),
+			)
+		);
+	}
+	if expandImplications(context, &vec![para.dst]).await?.contains(&para.src) {
+		return Err(
+			juniper::FieldError::new(
+				"INCORRECT_REQUEST",
+				graphql_value!({
+					"Tag implications can not form a cycle"
+				}),
+			)
+		);
+	}
+	let result = postJSON!(EmptyJSON, format!("{}/tags/implications/add.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		Ok(true)
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn removeTagImplication_impl(context: &Context, para: TagImplicationParameters) -> FieldResult<bool> {
+	let result = postJSON!(EmptyJSON, format!("{}/tags/implications/remove.do", BACKEND_URL), para, context);
+	if result.status == "SUCCEED" {
+		Ok(true)
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+async fn postTagIds(context: &Context, path: &str, req: serde_json::Value) -> FieldResult<Vec<i32>> {
+	let result = postJSON!(TagImplicationsResult, format!("{}/tags/implications/{}", BACKEND_URL, path), req, context);
+	if result.status == "SUCCEED" {
+		Ok(result.data.unwrap().tagids)
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+/// Tags directly implied by `tagid` if `reverse` is false, otherwise tags directly implying `tagid`
+pub async fn listImplications_impl(context: &Context, tagid: i32, reverse: bool) -> FieldResult<Vec<TagObjectValue>> {
+	let path = if reverse { "implied_by.do" } else { "implies.do" };
+	let tagids = postTagIds(context, path, json!({
+		"tagid": tagid
+	})).await?;
+	if tagids.len() == 0 {
+		return Ok(vec![]);
+	}
+	editTags::getTagObjectsBatch_impl(context, editTags::GetTagObjectsBatchParameters {
+		tagid: tagids
+	}).await
+}
+
+/// All tags transitively implied by `tagids` and not already in it
+pub async fn expandImplications(context: &Context, tagids: &Vec<i32>) -> FieldResult<Vec<i32>> {
+	if tagids.len() == 0 {
+		return Ok(vec![]);
+	}
+	let implied = postTagIds(context, "expand.do", json!({
+		"tagids": tagids
+	})).await?;
+	Ok(implied.into_iter().filter(|t| !tagids.contains(t)).collect())
+}
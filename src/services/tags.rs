@@ -16,7 +16,7 @@ use crate::context::Context;
 #[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
 #[graphql(description="GetPopularTags parameters", Context = Context)]
 pub struct GetPopularTagsParameters {
-	/// Language, default 'ENG'
+	/// Language, default to request language
 	pub lang: Option<String>,
 	// How many tags, default 20
 	pub count: Option<i32>
@@ -48,7 +48,8 @@ impl GetPopularTagsResult {
 	}
 }
 
-pub async fn getPopularTags_impl(context: &Context, para: GetPopularTagsParameters) -> FieldResult<GetPopularTagsResult> {
+pub async fn getPopularTags_impl(context: &Context, mut para: GetPopularTagsParameters) -> FieldResult<GetPopularTagsResult> {
+	para.lang = Some(context.lang_or_default(para.lang.take()));
 	let result = postJSON!(GetPopularTagsResult, format!("{}/tags/popular_tags.do", BACKEND_URL), para, context);
 	if result.status == "SUCCEED" {
 		Ok(result.data.unwrap())
@@ -63,3 +64,83 @@ pub async fn getPopularTags_impl(context: &Context, para: GetPopularTagsParamete
 		)
 	}
 }
+
+/// Default num of autocomplete suggestions
+const DEFAULT_AUTOCOMPLETE_LIMIT: i32 = 10;
+/// Max num of autocomplete suggestions
+const MAX_AUTOCOMPLETE_LIMIT: i32 = 30;
+
+#[derive(Clone)]
+pub struct TagSuggestion {
+	pub tag: RegularTagObject,
+	pub matched: String,
+	pub matched_lang: Option<String>,
+	pub display_name: String
+}
+
+#[juniper::graphql_object(Context = Context)]
+#[graphql(description="Tag matching an autocomplete prefix")]
+impl TagSuggestion {
+	pub fn tagid(&self) -> i32 {
+		self.tag.tagid
+	}
+	pub fn category(&self) -> &TagCategoryEnum {
+		&self.tag.category
+	}
+	pub fn count(&self) -> i32 {
+		self.tag.count
+	}
+	/// Name or alias which starts with prefix
+	pub fn matched(&self) -> &String {
+		&self.matched
+	}
+	/// Language of matched name, null if an alias matched
+	pub fn matched_lang(&self) -> &Option<String> {
+		&self.matched_lang
+	}
+	/// Name of this tag in requested language
+	pub fn display_name(&self) -> &String {
+		&self.display_name
+	}
+	pub async fn tag(&self, context: &Context) -> FieldResult<Option<TagObjectValue>> {
+		Ok(super::editTags::getTagObjectsBatch_impl(context, super::editTags::GetTagObjectsBatchParameters {
+			tagid: vec![self.tag.tagid]
+		}).await?.into_iter().next())
+	}
+}
+
+pub async fn autocompleteTags_impl(context: &Context, prefix: String, lang: Option<String>, category: Option<TagCategoryEnum>, limit: Option<i32>) -> FieldResult<Vec<TagSuggestion>> {
+	let prefix = prefix.trim().replace(|c: char| c == '*' || c == '?', "");
+	if prefix.len() == 0 {
+		return Ok(vec![]);
+	}
+	let limit = limit.unwrap_or(DEFAULT_AUTOCOMPLETE_LIMIT).max(1).min(MAX_AUTOCOMPLETE_LIMIT);
+	let found = super::editTags::listTags_impl(context, super::editTags::ListTagParameters {
+		query: Some(format!("{}*", prefix)),
+		query_regex: Some(false),
+		category: category.map(|c| format!("{:?}", c)),
+		order: Some("count".to_string()),
+		offset: Some(0),
+		limit: Some(limit * 2)
+	}).await?;
+	let lang = context.lang_or_default(lang);
+	let lower = prefix.to_lowercase();
+	let starts = |s: &String| s.to_lowercase().starts_with(&lower);
+	let fallback = default_lang_fallback();
+	let mut ret = found.tags.into_iter().filter_map(|t| {
+		let (matched, matched_lang) = t.languages.iter().find(|m| m.lang == lang && starts(&m.value))
+			.or(t.languages.iter().find(|m| starts(&m.value)))
+			.map(|m| (m.value.clone(), Some(m.lang.clone())))
+			.or(t.alias.iter().find(|a| starts(a)).map(|a| (a.clone(), None)))?;
+		let display_name = pick_language(&t.languages, &lang, &fallback).map_or(matched.clone(), |m| m.value.clone());
+		Some(TagSuggestion {
+			tag: t,
+			matched: matched,
+			matched_lang: matched_lang,
+			display_name: display_name
+		})
+	}).collect::<Vec<_>>();
+	ret.sort_by(|a, b| b.tag.count.cmp(&a.tag.count).then(a.matched.len().cmp(&b.matched.len())));
+	ret.truncate(limit as usize);
+	Ok(ret)
+}
@@ -0,0 +1,356 @@
+
+use juniper::{graphql_value, InputValue, ParseScalarResult, ParseScalarValue, ScalarValue, Value, parser::ScalarToken};
+
+
+use juniper::FieldResult;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+
+use actix_multipart::{Field, Multipart};
+use actix_web::{http::HeaderMap, web};
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+
+use crate::common::*;
+use crate::config::Config;
+
+use serde_derive::{Serialize, Deserialize};
+use crate::models::{Error, RestResult, Playlist};
+use crate::context::Context;
+use crate::services::{playlist, users};
+
+/// Refers to a file in the multipart request by its name in `map`
+#[derive(Debug, Clone)]
+pub struct Upload(pub String);
+
+#[juniper::graphql_scalar(description = "A file sent as a part of GraphQL multipart request")]
+impl<S> GraphQLScalar for Upload
+where
+	S: ScalarValue
+{
+	fn resolve(&self) -> Value {
+		Value::scalar(self.0.clone())
+	}
+
+	fn from_input_value(value: &InputValue) -> Option<Upload> {
+		value.as_string_value().map(|s| Upload(s.to_owned()))
+	}
+
+	fn from_str<'a>(value: ScalarToken<'a>) -> ParseScalarResult<'a, S> {
+		<String as ParseScalarValue<S>>::from_str(value)
+	}
+}
+
+/// A file of the multipart request, already streamed to backend
+#[derive(Debug, Clone)]
+pub struct UploadedFile {
+	pub filename: String,
+	/// Recognised from file content, not the Content-Type sent by client
+	pub content_type: String,
+	/// Key backend assigned to this file
+	pub file_key: String
+}
+
+#[derive(Debug)]
+pub enum MultipartRequestError {
+	Malformed(String),
+	TooLarge,
+	TooManyFiles,
+	UnsupportedMediaType(String),
+	Backend(String)
+}
+
+impl fmt::Display for MultipartRequestError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			MultipartRequestError::Malformed(reason) => write!(f, "MALFORMED_MULTIPART_REQUEST: {}", reason),
+			MultipartRequestError::TooLarge => write!(f, "FILE_TOO_LARGE"),
+			MultipartRequestError::TooManyFiles => write!(f, "TOO_MANY_FILES"),
+			MultipartRequestError::UnsupportedMediaType(mime) => write!(f, "UNSUPPORTED_MEDIA_TYPE: {}", mime),
+			MultipartRequestError::Backend(reason) => write!(f, "UPLOAD_FAILED: {}", reason),
+		}
+	}
+}
+
+fn malformed<E: fmt::Display>(e: E) -> MultipartRequestError {
+	MultipartRequestError::Malformed(e.to_string())
+}
+
+/// Set value at a dot separated path such as `variables.files.0`
+fn set_at_path(root: &mut serde_json::Value, path: &str, value: serde_json::Value) -> Result<(), MultipartRequestError> {
+	let segments = path.split('.').collect::<Vec<_>>();
+	let mut cur = root;
+	for seg in segments.iter() {
+		cur = match cur {
+			serde_json::Value::Object(o) => o.get_mut(*seg),
+			serde_json::Value::Array(a) => seg.parse::<usize>().ok().and_then(move |i| a.get_mut(i)),
+			_ => None
+		}.ok_or(MultipartRequestError::Malformed(format!("invalid path '{}' in map", path)))?;
+	}
+	*cur = value;
+	Ok(())
+}
+
+/// Num of leading bytes needed to recognise an image type
+const SNIFF_BYTES: usize = 12;
+
+/// Recognise image type from the first bytes of a file
+fn sniff_image_type(head: &[u8]) -> Option<&'static str> {
+	if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+		Some("image/png")
+	} else if head.starts_with(&[0xff, 0xd8, 0xff]) {
+		Some("image/jpeg")
+	} else if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+		Some("image/gif")
+	} else if head.len() >= 12 && &head[0..4] == b"RIFF" && &head[8..12] == b"WEBP" {
+		Some("image/webp")
+	} else {
+		None
+	}
+}
+
+/// A GraphQL multipart request whose `operations` and `map` are read but files are not
+pub struct MultipartRequest {
+	multipart: Multipart,
+	/// Operations with every file replaced by its name in `map`
+	pub operations: serde_json::Value,
+	map: HashMap<String, Vec<String>>
+}
+
+impl MultipartRequest {
+	/// Read `operations` and `map`, which the spec requires to precede all files
+	pub async fn begin(headers: &HeaderMap, payload: web::Payload, config: &Config) -> Result<MultipartRequest, MultipartRequestError> {
+		let mut multipart = Multipart::new(headers, payload);
+		let mut operations: Option<serde_json::Value> = None;
+		let mut map: Option<HashMap<String, Vec<String>>> = None;
+		while operations.is_none() || map.is_none() {
+			let mut field = match multipart.next().await {
+				Some(field) => field.map_err(malformed)?,
+				None => break
+			};
+			let disposition = field.content_disposition().ok_or(MultipartRequestError::Malformed("missing content disposition".to_string()))?;
+			let name = disposition.get_name().ok_or(MultipartRequestError::Malformed("missing field name".to_string()))?.to_string();
+			if name != "operations" && name != "map" {
+				return Err(MultipartRequestError::Malformed("operations and map must precede files".to_string()));
+			}
+			let mut data: Vec<u8> = vec![];
+			while let Some(chunk) = field.next().await {
+				let chunk = chunk.map_err(malformed)?;
+				if data.len() + chunk.len() > config.upload_max_bytes {
+					return Err(MultipartRequestError::TooLarge);
+				}
+				data.extend_from_slice(&chunk);
+			}
+			if name == "operations" {
+				operations = Some(serde_json::from_slice(&data).map_err(malformed)?);
+			} else {
+				map = Some(serde_json::from_slice(&data).map_err(malformed)?);
+			}
+		}
+		let mut operations = operations.ok_or(MultipartRequestError::Malformed("missing operations".to_string()))?;
+		let map = map.ok_or(MultipartRequestError::Malformed("missing map".to_string()))?;
+		if map.len() > config.upload_max_files {
+			return Err(MultipartRequestError::TooManyFiles);
+		}
+		for (key, paths) in map.iter() {
+			for path in paths.iter() {
+				set_at_path(&mut operations, path, serde_json::Value::String(key.clone()))?;
+			}
+		}
+		Ok(MultipartRequest {
+			multipart: multipart,
+			operations: operations,
+			map: map
+		})
+	}
+
+	/// Stream every file to backend as it arrives, returns files keyed by their name in `map`
+	pub async fn upload_files(mut self, context: &Context, config: &Config) -> Result<HashMap<String, UploadedFile>, MultipartRequestError> {
+		let mut files = HashMap::new();
+		while let Some(field) = self.multipart.next().await {
+			let mut field = field.map_err(malformed)?;
+			let disposition = field.content_disposition().ok_or(MultipartRequestError::Malformed("missing content disposition".to_string()))?;
+			let name = disposition.get_name().ok_or(MultipartRequestError::Malformed("missing field name".to_string()))?.to_string();
+			let filename = disposition.get_filename().unwrap_or("").to_string();
+			if !self.map.contains_key(&name) || files.contains_key(&name) {
+				return Err(MultipartRequestError::Malformed(format!("file '{}' not in map", name)));
+			}
+			let file = stream_file(context, &mut field, filename, config).await?;
+			files.insert(name, file);
+		}
+		Ok(files)
+	}
+}
+
+/// Stream a file part to backend, size and type limits are enforced while streaming
+async fn stream_file(context: &Context, field: &mut Field, filename: String, config: &Config) -> Result<UploadedFile, MultipartRequestError> {
+	let mut head = web::BytesMut::new();
+	while head.len() < SNIFF_BYTES {
+		match field.next().await {
+			Some(chunk) => head.extend_from_slice(&chunk.map_err(malformed)?),
+			None => break
+		}
+	}
+	if head.len() > config.upload_max_bytes {
+		return Err(MultipartRequestError::TooLarge);
+	}
+	let content_type = match sniff_image_type(&head) {
+		Some(t) if config.upload_allowed_mime.iter().any(|m| m == t) => t,
+		Some(t) => return Err(MultipartRequestError::UnsupportedMediaType(t.to_string())),
+		None => return Err(MultipartRequestError::UnsupportedMediaType(field.content_type().essence_str().to_string()))
+	};
+	let (mut tx, rx) = mpsc::channel::<Result<web::Bytes, std::io::Error>>(4);
+	let feed = async move {
+		let mut size = head.len();
+		if tx.send(Ok(head.freeze())).await.is_err() {
+			return Ok(());
+		}
+		while let Some(chunk) = field.next().await {
+			let chunk = chunk.map_err(malformed)?;
+			size += chunk.len();
+			if size > config.upload_max_bytes {
+				// failing the body aborts the backend request
+				let _ = tx.send(Err(std::io::Error::new(std::io::ErrorKind::Other, "FILE_TOO_LARGE"))).await;
+				return Err(MultipartRequestError::TooLarge);
+			}
+			if tx.send(Ok(chunk)).await.is_err() {
+				// backend stopped reading, its response tells why
+				break;
+			}
+		}
+		Ok(())
+	};
+	let part = reqwest::multipart::Part::stream(reqwest::Body::wrap_stream(rx))
+		.file_name(filename.clone())
+		.mime_str(content_type)
+		.map_err(malformed)?;
+	let (fed, uploaded) = futures::join!(feed, uploadImage_impl(context, part));
+	fed?;
+	let file_key = uploaded.map_err(|e| MultipartRequestError::Backend(e.message().to_string()))?;
+	Ok(UploadedFile {
+		filename: filename,
+		content_type: content_type.to_string(),
+		file_key: file_key
+	})
+}
+
+pub fn get_upload<'a>(context: &'a Context, file: &Upload) -> FieldResult<&'a UploadedFile> {
+	context.uploads.get(&file.0).ok_or(juniper::FieldError::new(
+		"INCORRECT_REQUEST",
+		graphql_value!({
+			"File not found in multipart request"
+		}),
+	))
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UploadImageResult {
+	pub file_key: String
+}
+
+/// Send a file to backend, returns the file key backend assigned to it
+pub async fn uploadImage_impl(context: &Context, part: reqwest::multipart::Part) -> FieldResult<String> {
+	let form = reqwest::multipart::Form::new().part("file", part);
+	let client = reqwest::Client::new();
+	let client = client.post(&format!("{}/helper/upload_image.do", BACKEND_URL));
+	let client = match context.session.as_ref() {
+		Some(sess) => client.header("cookie", format!("session={}", sess)),
+		None => client
+	};
+	let client = match context.auth_header.as_ref() {
+		Some(auth) => client.header("Authorization", auth),
+		None => client
+	};
+	let response = client.multipart(form).send().await?;
+	if response.status().is_success() {
+		let result: RestResult<UploadImageResult> = response.json().await?;
+		if result.status == "SUCCEED" {
+			Ok(result.data.unwrap().file_key)
+		} else {
+			let reason = result.dataerr.map_or("UNKNOWN".to_owned(), |f| f.reason);
+			Err(
+				juniper::FieldError::new(
+					result.status,
+					graphql_value!({
+						reason
+					}),
+				)
+			)
+		}
+	} else {
+		let e: Error = response.json().await?;
+		Err(
+			juniper::FieldError::new(
+				e.code,
+				graphql_value!({
+					e.aux
+				}),
+			)
+		)
+	}
+}
+
+pub async fn uploadAvatar_impl(context: &Context, file: Upload) -> FieldResult<users::User> {
+	let file_key = &get_upload(context, &file)?.file_key;
+	let req = json!({
+		"file_key": file_key
+	});
+	let result = postJSON!(EmptyJSON, format!("{}/user/changephoto.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		*context.viewer.lock().await = None;
+		match users::viewer_impl(context).await? {
+			Some(v) => Ok(v.user),
+			None => Err(
+				juniper::FieldError::new(
+					"UNAUTHORISED_OPERATION",
+					graphql_value!({
+						"aa"
+					}),
+				)
+			)
+		}
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+pub async fn setPlaylistCover_impl(context: &Context, pid: String, file: Upload) -> FieldResult<Playlist> {
+	let file_key = &get_upload(context, &file)?.file_key;
+	let req = json!({
+		"pid": pid,
+		"file_key": file_key
+	});
+	let result = postJSON!(EmptyJSON, format!("{}/lists/set_cover.do", BACKEND_URL), req, context);
+	if result.status == "SUCCEED" {
+		playlist::getPlaylist_impl(context, playlist::GetPlaylistParameters {
+			pid: pid
+		}).await
+	} else {
+		Err(
+			juniper::FieldError::new(
+				result.status,
+				graphql_value!({
+					"aa"
+				}),
+			)
+		)
+	}
+}
+
+#[test]
+fn sniff_image_types() {
+	assert_eq!(sniff_image_type(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("image/png"));
+	assert_eq!(sniff_image_type(&[0xff, 0xd8, 0xff, 0xe0, 0, 0x10]), Some("image/jpeg"));
+	assert_eq!(sniff_image_type(b"GIF89a\x01\0"), Some("image/gif"));
+	assert_eq!(sniff_image_type(b"RIFF\x24\0\0\0WEBPVP8 "), Some("image/webp"));
+	assert_eq!(sniff_image_type(b"<svg xmlns="), None);
+	assert_eq!(sniff_image_type(b""), None);
+}
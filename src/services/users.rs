@@ -5,14 +5,14 @@ use juniper::graphql_value;
 use juniper::FieldResult;
 
 use crate::models::TagObjectValue;
-use crate::services::editTags;
+use crate::services::{editTags, notification, playlist, pvsubscription};
 use crate::{common::*};
 
 use chrono::{DateTime, Utc};
 use serde_derive::{Serialize, Deserialize};
 use bson::oid::ObjectId;
 use std::convert::{TryFrom, TryInto};
-use crate::models::{Meta, Error, RestResult, Video, PlaylistMeta};
+use crate::models::{Meta, Error, RestResult, Video, Playlist, PlaylistMeta, typed_backend_error};
 use crate::context::Context;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -55,6 +55,15 @@ impl User {
 	pub fn meta(&self) -> &Meta {
 		&self.meta
 	}
+	/// Public playlists created by this user
+	pub async fn playlists(&self, context: &Context, offset: Option<i32>, limit: Option<i32>, order: Option<String>) -> FieldResult<playlist::ListPlaylistResult> {
+		playlist::listUserPlaylists_impl(context, playlist::ListUserPlaylistsParameters {
+			uid: self._id.to_string(),
+			offset: offset,
+			limit: limit,
+			order: order
+		}).await
+	}
 	pub async fn linked_tagid_object(&self, context: &Context) -> FieldResult<Option<TagObjectValue>> {
 		Ok(match self.linked_tagid {
 			Some(tag_id) => {
@@ -125,6 +134,165 @@ pub async fn getUser_impl(context: &Context, para: GetUserParameters) -> FieldRe
 	}
 }
 
+#[derive(juniper::GraphQLEnum, Clone, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub enum UserRoleEnum {
+	Normal,
+	Admin,
+	Blocked
+}
+
+impl UserRoleEnum {
+	pub fn from_string(status: &str) -> FieldResult<UserRoleEnum> {
+		match status {
+			"normal" => Ok(UserRoleEnum::Normal),
+			"admin" => Ok(UserRoleEnum::Admin),
+			"blocked" => Ok(UserRoleEnum::Blocked),
+			_ => Err(juniper::FieldError::new(
+				"UNKNOWN_USER_STATUS",
+				graphql_value!({
+					"aa"
+				}),
+			))
+		}
+	}
+	/// Position of this role in Blocked < Normal < Admin
+	fn level(&self) -> i32 {
+		match self {
+			UserRoleEnum::Blocked => 0,
+			UserRoleEnum::Normal => 1,
+			UserRoleEnum::Admin => 2
+		}
+	}
+}
+
+impl PartialOrd for UserRoleEnum {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for UserRoleEnum {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		self.level().cmp(&other.level())
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessControl {
+	/// One of 'normal', 'admin', 'blocked'
+	pub status: String,
+	/// Highest clearence of items this user can see and set, missing if backend grants none
+	pub clearence: Option<i32>,
+	pub access_mode: Option<String>,
+	pub allowed_ops: Option<Vec<String>>,
+	pub denied_ops: Option<Vec<String>>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GetMyProfileResult {
+	pub profile: UserProfile,
+	pub access_control: AccessControl,
+	pub linked_tagid: Option<i32>,
+	pub _id: ObjectId,
+	pub meta: Meta,
+}
+
+#[derive(Debug, Clone)]
+pub struct Viewer {
+	pub user: User,
+	pub role: UserRoleEnum,
+	pub access_control: AccessControl
+}
+
+impl Viewer {
+	/// Clearence reported by backend, 0 if none is reported
+	pub fn max_clearence(&self) -> i32 {
+		self.access_control.clearence.unwrap_or(0)
+	}
+}
+
+#[juniper::graphql_object(Context = Context)]
+#[graphql(description="Currently logged in user")]
+impl Viewer {
+	pub fn user(&self) -> &User {
+		&self.user
+	}
+	pub fn role(&self) -> &UserRoleEnum {
+		&self.role
+	}
+	/// Highest clearence of items this user can see and set
+	pub fn clearence(&self) -> i32 {
+		self.max_clearence()
+	}
+	/// Operations explicitly allowed for this user
+	pub fn allowed_ops(&self) -> Vec<String> {
+		self.access_control.allowed_ops.clone().unwrap_or_default()
+	}
+	/// Operations explicitly denied for this user
+	pub fn denied_ops(&self) -> Vec<String> {
+		self.access_control.denied_ops.clone().unwrap_or_default()
+	}
+	pub async fn unread_notifications_count(&self, context: &Context) -> FieldResult<notification::ListUnreadNotificationCountGQLResult> {
+		notification::listUnreadNotificationCount_impl(context).await
+	}
+	pub async fn subscriptions(&self, context: &Context) -> FieldResult<Vec<pvsubscription::PVSubscription>> {
+		pvsubscription::listSubscriptions_impl(context).await
+	}
+	/// Playlists created by this user, including private ones
+	pub async fn playlists(&self, context: &Context, offset: Option<i32>, limit: Option<i32>, order: Option<String>) -> FieldResult<playlist::ListPlaylistResult> {
+		playlist::listMyPlaylists_impl(context, playlist::ListMyPlaylistsParameters {
+			offset: offset,
+			limit: limit,
+			order: order
+		}).await
+	}
+	/// Favourites (watch later) playlist of this user
+	pub async fn favourites(&self, context: &Context) -> FieldResult<Playlist> {
+		playlist::getFavourites_impl(context).await
+	}
+}
+
+/// Reason reported by backend when the request is not logged in
+const NOT_LOGGED_IN_REASON: &str = "UNAUTHORISED_OPERATION";
+
+/// Returns None if not logged in, result is cached in context, backend failures are not cached
+pub async fn viewer_impl(context: &Context) -> FieldResult<Option<Viewer>> {
+	// held until resolved so concurrent resolvers wait for a single backend call
+	let mut cache = context.viewer.lock().await;
+	if let Some(cached) = cache.as_ref() {
+		return Ok(cached.clone());
+	}
+	if context.session.is_none() && context.auth_header.is_none() {
+		*cache = Some(None);
+		return Ok(None);
+	}
+	let result = postJSON!(GetMyProfileResult, format!("{}/user/myprofile.do", BACKEND_URL), EmptyJSON::new(), context);
+	let viewer = if result.status == "SUCCEED" {
+		let r = result.data.unwrap();
+		Some(Viewer {
+			user: User {
+				_id: r._id,
+				bind_qq: r.profile.bind_qq,
+				desc: r.profile.desc,
+				username: r.profile.username,
+				email: r.profile.email,
+				image: r.profile.image,
+				meta: r.meta,
+				gravatar: r.profile.gravatar,
+				linked_tagid: r.linked_tagid
+			},
+			role: UserRoleEnum::from_string(&r.access_control.status)?,
+			access_control: r.access_control
+		})
+	} else if result.dataerr.as_ref().map_or(false, |e| e.reason == NOT_LOGGED_IN_REASON) {
+		None
+	} else {
+		return Err(typed_backend_error(result.status, result.dataerr));
+	};
+	*cache = Some(viewer.clone());
+	Ok(viewer)
+}
+
 pub async fn whoami_impl(context: &Context) -> FieldResult<String> {
 	let result = postJSON!(String, format!("{}/user/whoami", BACKEND_URL), EmptyJSON::new(), context);
 	if result.status == "SUCCEED" {
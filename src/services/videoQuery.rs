@@ -0,0 +1,150 @@
+
+use juniper::graphql_value;
+
+
+use juniper::FieldResult;
+
+use chrono::NaiveDate;
+use serde_derive::{Serialize, Deserialize};
+use crate::context::Context;
+
+/// Words with special meaning in tag query language
+const QUERY_KEYWORDS: [&str; 4] = ["AND", "OR", "NOT", "ANY"];
+/// Max depth of a VideoQueryInput tree
+const MAX_QUERY_DEPTH: usize = 16;
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize, Debug)]
+#[graphql(description="Structured tag query, exactly one field must be set", Context = Context)]
+pub struct VideoQueryInput {
+	/// All sub-queries must match
+	pub and: Option<Vec<VideoQueryInput>>,
+	/// Any of sub-queries must match
+	pub or: Option<Vec<VideoQueryInput>>,
+	/// Sub-query must not match
+	pub not: Option<Box<VideoQueryInput>>,
+	/// Tag name in any language, or tag alias
+	pub tag: Option<String>,
+	/// Only videos from this site, e.g. 'bilibili'
+	pub site: Option<String>,
+	/// Only videos uploaded in this date range
+	pub date: Option<DateRangeInput>,
+	/// Only videos with clearence no more than this value
+	pub clearence: Option<i32>
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize, Debug)]
+#[graphql(description="Date range, both ends are inclusive and in format YYYY-MM-DD", Context = Context)]
+pub struct DateRangeInput {
+	pub from: Option<String>,
+	pub to: Option<String>
+}
+
+fn invalid_query(reason: &str) -> juniper::FieldError {
+	juniper::FieldError::new(
+		"INCORRECT_REQUEST",
+		graphql_value!({
+			"reason": reason
+		}),
+	)
+}
+
+/// Keyword `word` is read as, backend matches keywords case-insensitively
+pub fn query_keyword(word: &str) -> Option<&'static str> {
+	QUERY_KEYWORDS.iter().find(|k| k.eq_ignore_ascii_case(word)).copied()
+}
+
+/// Quote a term if it would otherwise be read as an operator, filter or parenthesis
+pub fn escape_term(term: &str) -> String {
+	let needs_quote = term.len() == 0
+		|| term.chars().any(|c| c.is_whitespace() || c == '(' || c == ')' || c == '"' || c == '\\' || c == ':')
+		|| query_keyword(term).is_some();
+	if needs_quote {
+		format!("\"{}\"", term.replace('\\', "\\\\").replace('"', "\\\""))
+	} else {
+		term.to_string()
+	}
+}
+
+fn check_date(date: &str) -> FieldResult<String> {
+	NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| invalid_query("Date must be in format YYYY-MM-DD"))?;
+	Ok(date.to_string())
+}
+
+fn compile_node(node: &VideoQueryInput, depth: usize) -> FieldResult<String> {
+	if depth > MAX_QUERY_DEPTH {
+		return Err(invalid_query("Query is nested too deep"));
+	}
+	let num_set = [node.and.is_some(), node.or.is_some(), node.not.is_some(), node.tag.is_some(), node.site.is_some(), node.date.is_some(), node.clearence.is_some()].iter().filter(|&&b| b).count();
+	if num_set != 1 {
+		return Err(invalid_query("Exactly one field of VideoQueryInput must be set"));
+	}
+	let join = |children: &Vec<VideoQueryInput>, op: &str| -> FieldResult<String> {
+		if children.len() == 0 {
+			return Err(invalid_query("AND and OR require at least one sub-query"));
+		}
+		let parts = children.iter().map(|c| compile_node(c, depth + 1)).collect::<FieldResult<Vec<_>>>()?;
+		Ok(if parts.len() == 1 { parts[0].clone() } else { format!("({})", parts.join(&format!(" {} ", op))) })
+	};
+	if let Some(children) = node.and.as_ref() {
+		join(children, "AND")
+	} else if let Some(children) = node.or.as_ref() {
+		join(children, "OR")
+	} else if let Some(child) = node.not.as_ref() {
+		Ok(format!("NOT {}", compile_node(child, depth + 1)?))
+	} else if let Some(tag) = node.tag.as_ref() {
+		if tag.trim().len() == 0 {
+			return Err(invalid_query("Tag must not be empty"));
+		}
+		Ok(escape_term(tag.trim()))
+	} else if let Some(site) = node.site.as_ref() {
+		Ok(format!("site:{}", escape_term(site.trim())))
+	} else if let Some(range) = node.date.as_ref() {
+		match (range.from.as_ref(), range.to.as_ref()) {
+			(Some(from), Some(to)) => Ok(format!("(date:>={} AND date:<={})", check_date(from)?, check_date(to)?)),
+			(Some(from), None) => Ok(format!("date:>={}", check_date(from)?)),
+			(None, Some(to)) => Ok(format!("date:<={}", check_date(to)?)),
+			(None, None) => Err(invalid_query("Date range needs at least one end"))
+		}
+	} else {
+		let clearence = node.clearence.unwrap();
+		if clearence < 0 || clearence > 3 {
+			return Err(invalid_query("Clearence must be between 0 and 3"));
+		}
+		Ok(format!("clearence:<={}", clearence))
+	}
+}
+
+/// Compile a structured query into backend tag query string
+pub fn compile_query(query: &VideoQueryInput) -> FieldResult<String> {
+	compile_node(query, 0)
+}
+
+/// Pick raw query string or compiled query tree, at most one of them can be given
+pub fn merge_query(raw: Option<String>, tree: Option<&VideoQueryInput>) -> FieldResult<Option<String>> {
+	match (raw, tree) {
+		(Some(_), Some(_)) => Err(invalid_query("Only one of raw query string and query tree can be given")),
+		(raw, None) => Ok(raw),
+		(None, Some(tree)) => Ok(Some(compile_query(tree)?))
+	}
+}
+
+#[cfg(test)]
+fn tag_node(tag: &str) -> VideoQueryInput {
+	VideoQueryInput { and: None, or: None, not: None, tag: Some(tag.to_string()), site: None, date: None, clearence: None }
+}
+
+#[test]
+fn compile_video_query() {
+	let mut site = tag_node("");
+	site.tag = None;
+	site.site = Some("bilibili".to_string());
+	let mut not = tag_node("");
+	not.tag = None;
+	not.not = Some(Box::new(tag_node("or")));
+	let mut root = tag_node("");
+	root.tag = None;
+	root.and = Some(vec![tag_node("東方"), tag_node("say \"hi\" (live)"), site, not]);
+	assert_eq!(compile_query(&root).unwrap(), "(東方 AND \"say \\\"hi\\\" (live)\" AND site:bilibili AND NOT \"or\")");
+	root.tag = Some("x".to_string());
+	assert!(compile_query(&root).is_err());
+}
@@ -0,0 +1,340 @@
+
+use juniper::graphql_value;
+
+
+use juniper::FieldResult;
+use reqwest::Url;
+use serde_json::json;
+
+use crate::common::*;
+
+use serde_derive::{Serialize, Deserialize};
+use crate::models::{Error, RestResult, Video};
+use crate::context::Context;
+
+/// Max num of URLs checked in one checkVideoExists query
+const MAX_CHECK_URLS: usize = 100;
+
+#[derive(juniper::GraphQLObject, Clone, Debug, PartialEq)]
+#[graphql(description="Video URL resolved locally, fields match VideoItem's site and unique_id", Context = Context)]
+pub struct ResolvedVideoUrl {
+	/// One of 'bilibili', 'youtube', 'nicovideo', 'twitter', 'acfun', 'zcool'
+	pub site: String,
+	pub canonical_url: String,
+	pub unique_id: String
+}
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VideoUrlErrorEnum {
+	/// Not a valid URL
+	Malformed,
+	/// Site is not supported, or URL is a short link which can not be resolved without network access
+	UnsupportedSite,
+	/// Site is supported but no video ID was found in URL
+	MissingVideoId
+}
+
+#[derive(juniper::GraphQLObject, Clone, Debug, PartialEq)]
+#[graphql(description="Reason a video URL can not be resolved", Context = Context)]
+pub struct VideoUrlParseError {
+	pub code: VideoUrlErrorEnum,
+	pub url: String
+}
+
+#[derive(juniper::GraphQLUnion, Clone, Debug, PartialEq)]
+#[graphql(Context = Context)]
+pub enum ResolveVideoUrlResult {
+	Resolved(ResolvedVideoUrl),
+	Error(VideoUrlParseError)
+}
+
+const BV_TABLE: &[u8] = b"FcwAPNKTMug3GV5Lj7EJnHpWsx4tb8haYeviqBz6rkCy12mUSDQX9RdoZf";
+const BV_XOR_CODE: u64 = 23442827791579;
+const BV_MASK_CODE: u64 = 2251799813685247;
+const BV_MAX_AID: u64 = 1 << 51;
+
+/// Convert bilibili BV ID such as 'BV17x411w7KC' to its aid
+pub fn bv_to_aid(bvid: &str) -> Option<u64> {
+	let mut chars = bvid.as_bytes().to_vec();
+	if chars.len() != 12 || !bvid[..2].eq_ignore_ascii_case("BV") {
+		return None;
+	}
+	chars.swap(3, 9);
+	chars.swap(4, 7);
+	let mut tmp: u64 = 0;
+	for c in chars[3..].iter() {
+		let idx = BV_TABLE.iter().position(|t| t == c)? as u64;
+		tmp = tmp.checked_mul(58)?.checked_add(idx)?;
+	}
+	Some((tmp & BV_MASK_CODE) ^ BV_XOR_CODE)
+}
+
+/// Convert bilibili aid to BV ID
+pub fn aid_to_bv(aid: u64) -> String {
+	let mut bytes = b"BV1000000000".to_vec();
+	let mut idx = bytes.len() - 1;
+	let mut tmp = (BV_MAX_AID | aid) ^ BV_XOR_CODE;
+	while tmp > 0 && idx > 2 {
+		bytes[idx] = BV_TABLE[(tmp % 58) as usize];
+		tmp /= 58;
+		idx -= 1;
+	}
+	bytes.swap(3, 9);
+	bytes.swap(4, 7);
+	String::from_utf8(bytes).unwrap()
+}
+
+fn query_param(url: &Url, key: &str) -> Option<String> {
+	url.query_pairs().find(|(k, _)| k == key).map(|(_, v)| v.to_string())
+}
+
+fn path_segments(url: &Url) -> Vec<String> {
+	url.path_segments().map_or(vec![], |s| s.filter(|p| p.len() > 0).map(|p| p.to_string()).collect::<Vec<_>>())
+}
+
+fn is_digits(s: &str) -> bool {
+	s.len() > 0 && s.chars().all(|c| c.is_ascii_digit())
+}
+
+fn resolve_bilibili(url: &Url) -> Option<ResolvedVideoUrl> {
+	let segs = path_segments(url);
+	let vid = segs.iter().skip_while(|s| s.as_str() != "video").nth(1)?;
+	let aid = if vid.len() > 2 && vid[..2].eq_ignore_ascii_case("av") && is_digits(&vid[2..]) {
+		vid[2..].parse::<u64>().ok()?
+	} else {
+		bv_to_aid(vid)?
+	};
+	let page = query_param(url, "p").and_then(|p| p.parse::<u32>().ok()).filter(|p| *p > 0).unwrap_or(1);
+	Some(ResolvedVideoUrl {
+		site: "bilibili".to_string(),
+		canonical_url: if page > 1 {
+			format!("https://www.bilibili.com/video/av{}?p={}", aid, page)
+		} else {
+			format!("https://www.bilibili.com/video/av{}", aid)
+		},
+		unique_id: format!("bilibili:av{}-{}", aid, page)
+	})
+}
+
+fn is_youtube_id(id: &str) -> bool {
+	id.len() == 11 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn resolve_youtube(url: &Url, short: bool) -> Option<ResolvedVideoUrl> {
+	let segs = path_segments(url);
+	let id = if short {
+		segs.first().cloned()
+	} else if segs.first().map_or(false, |s| s == "watch") {
+		query_param(url, "v")
+	} else if segs.len() >= 2 && (segs[0] == "shorts" || segs[0] == "embed" || segs[0] == "v" || segs[0] == "live") {
+		Some(segs[1].clone())
+	} else {
+		None
+	}?;
+	if !is_youtube_id(&id) {
+		return None;
+	}
+	Some(ResolvedVideoUrl {
+		site: "youtube".to_string(),
+		canonical_url: format!("https://www.youtube.com/watch?v={}", id),
+		unique_id: format!("youtube:{}", id)
+	})
+}
+
+fn resolve_nicovideo(url: &Url, short: bool) -> Option<ResolvedVideoUrl> {
+	let segs = path_segments(url);
+	let id = if short {
+		segs.first()?
+	} else {
+		segs.iter().skip_while(|s| s.as_str() != "watch").nth(1)?
+	};
+	let valid = id.len() > 2 && (id.starts_with("sm") || id.starts_with("so") || id.starts_with("nm")) && is_digits(&id[2..]);
+	if !valid {
+		return None;
+	}
+	Some(ResolvedVideoUrl {
+		site: "nicovideo".to_string(),
+		canonical_url: format!("https://www.nicovideo.jp/watch/{}", id),
+		unique_id: format!("nicovideo:{}", id)
+	})
+}
+
+fn resolve_twitter(url: &Url) -> Option<ResolvedVideoUrl> {
+	let segs = path_segments(url);
+	let pos = segs.iter().position(|s| s == "status")?;
+	let user = if pos > 0 { segs[pos - 1].clone() } else { "i".to_string() };
+	let id = segs.get(pos + 1)?;
+	if !is_digits(id) {
+		return None;
+	}
+	Some(ResolvedVideoUrl {
+		site: "twitter".to_string(),
+		canonical_url: format!("https://twitter.com/{}/status/{}", user, id),
+		unique_id: format!("twitter:{}", id)
+	})
+}
+
+fn resolve_acfun(url: &Url) -> Option<ResolvedVideoUrl> {
+	let segs = path_segments(url);
+	let raw = match segs.iter().skip_while(|s| s.as_str() != "v").nth(1) {
+		Some(id) => id.clone(),
+		None => format!("ac{}", query_param(url, "ac")?)
+	};
+	let (id, part) = match raw.find('_') {
+		Some(i) => (raw[..i].to_string(), raw[i + 1..].parse::<u32>().ok().filter(|p| *p > 1)),
+		None => (raw.clone(), None)
+	};
+	if !(id.starts_with("ac") && is_digits(&id[2..])) {
+		return None;
+	}
+	let id = match part {
+		Some(p) => format!("{}_{}", id, p),
+		None => id
+	};
+	Some(ResolvedVideoUrl {
+		site: "acfun".to_string(),
+		canonical_url: format!("https://www.acfun.cn/v/{}", id),
+		unique_id: format!("acfun:{}", id)
+	})
+}
+
+fn resolve_zcool(url: &Url) -> Option<ResolvedVideoUrl> {
+	let segs = path_segments(url);
+	let id = segs.iter().skip_while(|s| s.as_str() != "work").nth(1)?.trim_end_matches(".html").to_string();
+	if id.len() == 0 || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '=') {
+		return None;
+	}
+	Some(ResolvedVideoUrl {
+		site: "zcool".to_string(),
+		canonical_url: format!("https://www.zcool.com.cn/work/{}.html", id),
+		unique_id: format!("zcool:{}", id)
+	})
+}
+
+/// Recognise a video URL without network access
+pub fn parse_video_url(raw: &str) -> Result<ResolvedVideoUrl, VideoUrlParseError> {
+	let raw = raw.trim();
+	let error = |code| VideoUrlParseError { code: code, url: raw.to_string() };
+	let with_scheme = if raw.contains("://") { raw.to_string() } else { format!("https://{}", raw) };
+	let url = Url::parse(&with_scheme).map_err(|_| error(VideoUrlErrorEnum::Malformed))?;
+	if url.scheme() != "http" && url.scheme() != "https" {
+		return Err(error(VideoUrlErrorEnum::Malformed));
+	}
+	let host = url.host_str().ok_or(error(VideoUrlErrorEnum::Malformed))?.to_ascii_lowercase();
+	let host = host.trim_start_matches("www.").trim_start_matches("m.").trim_start_matches("mobile.");
+	let resolved = match host {
+		"bilibili.com" => resolve_bilibili(&url),
+		"youtube.com" | "music.youtube.com" => resolve_youtube(&url, false),
+		"youtu.be" => resolve_youtube(&url, true),
+		"nicovideo.jp" | "sp.nicovideo.jp" => resolve_nicovideo(&url, false),
+		"nico.ms" => resolve_nicovideo(&url, true),
+		"twitter.com" | "x.com" => resolve_twitter(&url),
+		"acfun.cn" => resolve_acfun(&url),
+		"zcool.com.cn" => resolve_zcool(&url),
+		_ => return Err(error(VideoUrlErrorEnum::UnsupportedSite))
+	};
+	resolved.ok_or(error(VideoUrlErrorEnum::MissingVideoId))
+}
+
+pub fn resolveVideoUrl_impl(url: String) -> ResolveVideoUrlResult {
+	match parse_video_url(&url) {
+		Ok(r) => ResolveVideoUrlResult::Resolved(r),
+		Err(e) => ResolveVideoUrlResult::Error(e)
+	}
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="Whether a single URL was already posted", Context = Context)]
+pub struct VideoExistsResult {
+	/// URL as given
+	pub url: String,
+	/// Canonical URL, null if URL can not be resolved
+	pub canonical_url: Option<String>,
+	pub site: Option<String>,
+	pub unique_id: Option<String>,
+	/// Why URL can not be resolved
+	pub error: Option<VideoUrlErrorEnum>,
+	/// Existing video, use its `copies` and `playlists` fields to see where it is used
+	pub video: Option<Video>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FindVideosByUniqueIdResult {
+	pub videos: Vec<Video>
+}
+
+pub async fn checkVideoExists_impl(context: &Context, urls: Vec<String>) -> FieldResult<Vec<VideoExistsResult>> {
+	if urls.len() > MAX_CHECK_URLS {
+		return Err(
+			juniper::FieldError::new(
+				"INCORRECT_REQUEST",
+				graphql_value!({
+					"Too many URLs"
+				}),
+			)
+		);
+	}
+	let parsed = urls.iter().map(|u| parse_video_url(u)).collect::<Vec<_>>();
+	let mut unique_ids = parsed.iter().filter_map(|r| r.as_ref().ok()).map(|r| r.unique_id.clone()).collect::<Vec<_>>();
+	unique_ids.sort();
+	unique_ids.dedup();
+	let mut videos = vec![];
+	if unique_ids.len() > 0 {
+		let req = json!({
+			"unique_ids": unique_ids
+		});
+		let result = postJSON!(FindVideosByUniqueIdResult, format!("{}/videos/find_by_unique_id.do", BACKEND_URL), req, context);
+		if result.status == "SUCCEED" {
+			videos = result.data.unwrap().videos;
+		} else {
+			return Err(
+				juniper::FieldError::new(
+					result.status,
+					graphql_value!({
+						"aa"
+					}),
+				)
+			);
+		}
+	}
+	Ok(urls.into_iter().zip(parsed.into_iter()).map(|(url, r)| match r {
+		Ok(r) => VideoExistsResult {
+			url: url,
+			video: videos.iter().find(|v| v.item.site == r.site && v.item.unique_id == r.unique_id).cloned(),
+			canonical_url: Some(r.canonical_url),
+			site: Some(r.site),
+			unique_id: Some(r.unique_id),
+			error: None
+		},
+		Err(e) => VideoExistsResult {
+			url: url,
+			canonical_url: None,
+			site: None,
+			unique_id: None,
+			error: Some(e.code),
+			video: None
+		}
+	}).collect::<Vec<_>>())
+}
+
+#[test]
+fn bilibili_urls() {
+	assert_eq!(bv_to_aid("BV17x411w7KC"), Some(170001));
+	assert_eq!(aid_to_bv(170001), "BV17x411w7KC");
+	let av = parse_video_url("https://www.bilibili.com/video/av170001?p=2").unwrap();
+	let bv = parse_video_url("m.bilibili.com/video/BV17x411w7KC/?p=2").unwrap();
+	assert_eq!(av, bv);
+	assert_eq!(av.unique_id, "bilibili:av170001-2");
+}
+
+#[test]
+fn other_site_urls() {
+	assert_eq!(parse_video_url("https://youtu.be/dQw4w9WgXcQ").unwrap().unique_id, "youtube:dQw4w9WgXcQ");
+	assert_eq!(parse_video_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=1").unwrap().canonical_url, "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+	assert_eq!(parse_video_url("https://www.nicovideo.jp/watch/sm9").unwrap().unique_id, "nicovideo:sm9");
+	assert_eq!(parse_video_url("https://x.com/user/status/123456").unwrap().unique_id, "twitter:123456");
+	assert_eq!(parse_video_url("https://www.acfun.cn/v/ac4741185").unwrap().unique_id, "acfun:ac4741185");
+	assert_eq!(parse_video_url("https://example.com/video/1").unwrap_err().code, VideoUrlErrorEnum::UnsupportedSite);
+	assert_eq!(parse_video_url("https://www.youtube.com/feed").unwrap_err().code, VideoUrlErrorEnum::MissingVideoId);
+	assert_eq!(parse_video_url("http://[::1").unwrap_err().code, VideoUrlErrorEnum::Malformed);
+}